@@ -0,0 +1,353 @@
+/*!
+Compile time checked inline templates for [minimad](https://docs.rs/minimad).
+
+The [`mad_inline!`] macro of this crate is used like the one of minimad,
+but the markdown is parsed when compiling: a template with an unclosed
+style, or with arguments which don't match its placeholders, doesn't
+compile. It also supports named arguments.
+
+The generated code refers to `::minimad`, which must be a dependency of
+the crate using the macro.
+*/
+
+use {
+    proc_macro::TokenStream,
+    proc_macro2::{
+        Span,
+        TokenStream as TokenStream2,
+    },
+    quote::{
+        format_ident,
+        quote,
+    },
+    syn::{
+        Expr,
+        Ident,
+        LitStr,
+        Token,
+        parse::{
+            Parse,
+            ParseStream,
+        },
+        parse_macro_input,
+    },
+};
+
+/// the name of a named argument, given either as an identifier or,
+/// when it isn't a valid identifier (e.g. `"file-name"`), as a string
+struct ArgName {
+    name: String,
+    span: Span,
+}
+
+impl ArgName {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            let lit: LitStr = input.parse()?;
+            let name = lit.value();
+            if name.is_empty() || !name.chars().all(minimad::is_valid_name_char) {
+                return Err(syn::Error::new(lit.span(), "invalid argument name"));
+            }
+            Ok(Self {
+                name,
+                span: lit.span(),
+            })
+        } else {
+            let ident: Ident = input.parse()?;
+            Ok(Self {
+                name: ident.to_string(),
+                span: ident.span(),
+            })
+        }
+    }
+}
+
+/// an argument given to the macro
+struct Arg {
+    name: Option<ArgName>, // none for positional arguments
+    value: Expr,
+    used: bool,
+}
+
+struct MacroInput {
+    md: LitStr,
+    args: Vec<Arg>,
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let md: LitStr = input.parse()?;
+        let mut args: Vec<Arg> = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if (input.peek(Ident) || input.peek(LitStr))
+                && input.peek2(Token![=])
+                && !input.peek2(Token![==])
+            {
+                let name = ArgName::parse(input)?;
+                input.parse::<Token![=]>()?;
+                if args
+                    .iter()
+                    .any(|arg| arg.name.as_ref().is_some_and(|n| n.name == name.name))
+                {
+                    return Err(syn::Error::new(name.span, "duplicate argument"));
+                }
+                args.push(Arg {
+                    name: Some(name),
+                    value: input.parse()?,
+                    used: false,
+                });
+            } else {
+                let value: Expr = input.parse()?;
+                if args.iter().any(|arg| arg.name.is_some()) {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        "positional arguments must come before named ones",
+                    ));
+                }
+                args.push(Arg {
+                    name: None,
+                    value,
+                    used: false,
+                });
+            }
+        }
+        Ok(Self { md, args })
+    }
+}
+
+/// a part of a compound of the template
+enum Piece<'s> {
+    Text(&'s str),
+    Positional(usize),
+    Named(&'s str),
+}
+
+/// read the `{name}` part of a `${name}` placeholder starting at `idx`,
+/// and return the name with the index of its end
+///
+/// The allowed chars are the ones of `InlineTemplate` placeholders.
+fn read_name(
+    src: &str,
+    idx: usize,
+) -> Option<(&str, usize)> {
+    let name = src[idx..].strip_prefix('{')?;
+    let name_len = name.find('}')?;
+    let name = &name[..name_len];
+    if name.is_empty() || !name.chars().all(minimad::is_valid_name_char) {
+        return None;
+    }
+    Some((name, idx + name_len + 2))
+}
+
+/// split the content of a compound into text and placeholders, which
+/// are either positional (`$0`, `$12`) or named (`${name}`), like with
+/// `InlineTemplate`
+fn pieces(src: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut start = 0; // start of the text not yet handled
+    let mut idx = 0;
+    while idx < src.len() {
+        let placeholder = match src.as_bytes()[idx] {
+            b'$' => {
+                let digits_len = src[idx + 1..]
+                    .bytes()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                match src[idx + 1..idx + 1 + digits_len].parse() {
+                    Ok(num) => Some((Piece::Positional(num), idx + 1 + digits_len)),
+                    Err(_) => read_name(src, idx + 1).map(|(name, end)| (Piece::Named(name), end)),
+                }
+            }
+            _ => None,
+        };
+        let (piece, end) = match placeholder {
+            Some(placeholder) => placeholder,
+            None => {
+                idx += 1;
+                continue;
+            }
+        };
+        if start < idx {
+            pieces.push(Piece::Text(&src[start..idx]));
+        }
+        pieces.push(piece);
+        start = end;
+        idx = end;
+    }
+    if start < src.len() {
+        pieces.push(Piece::Text(&src[start..]));
+    }
+    pieces
+}
+
+/// tell whether a style of the template is still open at its end
+fn has_unclosed_style(md: &str) -> bool {
+    // a character added at the end would be styled
+    let probe = format!("{md}\u{1}");
+    let composite = minimad::Composite::from_inline(&probe);
+    composite
+        .compounds
+        .last()
+        .is_some_and(|c| c.bold || c.italic || c.code || c.strikeout)
+}
+
+fn link_tokens(link: Option<minimad::Link<'_>>) -> TokenStream2 {
+    match link {
+        Some(link) => {
+            let url = link.url;
+            let title = option_tokens(link.title);
+            let image = link.image;
+            let reference = option_tokens(link.reference);
+            let autolink = link.autolink;
+            quote! {
+                ::std::option::Option::Some(::minimad::Link {
+                    url: #url,
+                    title: #title,
+                    image: #image,
+                    reference: #reference,
+                    autolink: #autolink,
+                })
+            }
+        }
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+fn option_tokens(s: Option<&str>) -> TokenStream2 {
+    match s {
+        Some(s) => quote! { ::std::option::Option::Some(#s) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+fn expand(input: MacroInput) -> syn::Result<TokenStream2> {
+    let MacroInput { md, mut args } = input;
+    let src = md.value();
+    if has_unclosed_style(&src) {
+        return Err(syn::Error::new(md.span(), "unclosed style in template"));
+    }
+    let positional_count = args.iter().filter(|arg| arg.name.is_none()).count();
+    let bindings: Vec<Ident> = (0..args.len())
+        .map(|idx| format_ident!("arg_{}", idx, span = Span::mixed_site()))
+        .collect();
+    let composite = minimad::Composite::from_inline(&src);
+    let mut compounds = Vec::new();
+    for compound in &composite.compounds {
+        let bold = compound.bold;
+        let italic = compound.italic;
+        let code = compound.code;
+        let strikeout = compound.strikeout;
+        let link = link_tokens(compound.link);
+        for piece in pieces(compound.src) {
+            let value = match piece {
+                Piece::Text(text) => quote! { #text },
+                Piece::Positional(idx) => {
+                    if idx >= positional_count {
+                        return Err(syn::Error::new(
+                            md.span(),
+                            format!("no argument for `${idx}`"),
+                        ));
+                    }
+                    args[idx].used = true;
+                    let binding = &bindings[idx];
+                    quote! { #binding }
+                }
+                Piece::Named(name) => {
+                    let idx = args
+                        .iter()
+                        .position(|arg| arg.name.as_ref().is_some_and(|n| n.name == name))
+                        .ok_or_else(|| {
+                            syn::Error::new(md.span(), format!("no argument for `${{{name}}}`"))
+                        })?;
+                    args[idx].used = true;
+                    let binding = &bindings[idx];
+                    quote! { #binding }
+                }
+            };
+            compounds.push(quote! {
+                ::minimad::Compound {
+                    src: #value,
+                    bold: #bold,
+                    italic: #italic,
+                    code: #code,
+                    strikeout: #strikeout,
+                    link: #link,
+                    span: ::std::option::Option::None,
+                }
+            });
+        }
+    }
+    if let Some(arg) = args.iter().find(|arg| !arg.used) {
+        return Err(match &arg.name {
+            Some(name) => syn::Error::new(name.span, "argument never used"),
+            None => syn::Error::new_spanned(&arg.value, "argument never used"),
+        });
+    }
+    let values = args.iter().map(|arg| &arg.value);
+    Ok(quote! {
+        match (#(#values,)*) {
+            (#(#bindings,)*) => {
+                #(let #bindings: &str = #bindings;)*
+                ::minimad::Composite {
+                    style: ::minimad::CompositeStyle::Paragraph,
+                    compounds: ::std::vec![#(#compounds),*],
+                    quote_depth: 0,
+                }
+            }
+        }
+    })
+}
+
+/// build a composite from a string literal interpreted as markdown
+/// when compiling, and arguments filling the placeholders, either
+/// positional (`$0`, `$1`, etc.) or named (`${name}`).
+///
+/// Names are made of lowercase letters, digits, `_` and `-`, as with
+/// `InlineTemplate`. A name which isn't a Rust identifier is given as
+/// a string: `"file-name" = value`.
+///
+/// Like with minimad's `mad_inline!`, the arguments are `&str` which
+/// aren't interpreted as markdown. But the template is checked: there
+/// must be no unclosed style, each placeholder must have an argument,
+/// and each argument must be used.
+///
+/// ```
+/// use minimad_macros::mad_inline;
+///
+/// let composite = mad_inline!(
+///     "**${name}:** *$0*",
+///     "2*π*r", // the stars don't mess the markdown
+///     name = "Disk",
+/// );
+/// assert_eq!(composite.compounds.len(), 4);
+/// assert_eq!(composite.compounds[0].as_str(), "Disk");
+/// assert_eq!(composite.compounds[3].as_str(), "2*π*r");
+/// assert!(composite.compounds[3].italic);
+/// ```
+///
+/// A placeholder without argument is an error:
+/// ```compile_fail
+/// let composite = minimad_macros::mad_inline!("**${name}**: $0", "value");
+/// ```
+///
+/// So is an argument which isn't used:
+/// ```compile_fail
+/// let composite = minimad_macros::mad_inline!("**$0**", "a", "b");
+/// ```
+///
+/// Or a style which isn't closed:
+/// ```compile_fail
+/// let composite = minimad_macros::mad_inline!("**$0", "a");
+/// ```
+#[proc_macro]
+pub fn mad_inline(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MacroInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
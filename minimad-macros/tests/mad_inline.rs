@@ -0,0 +1,102 @@
+use {
+    minimad::*,
+    minimad_macros::mad_inline,
+};
+
+#[test]
+fn positional_arguments() {
+    let composite = mad_inline!("**$1:** `$0`", "π*r²", "area");
+    assert_eq!(
+        composite,
+        Composite::from(vec![
+            Compound::raw_str("area").bold(),
+            Compound::raw_str(":").bold(),
+            Compound::raw_str(" "),
+            Compound::raw_str("π*r²").code(),
+        ]),
+    );
+    assert_eq!(
+        mad_inline!("some `code`"),
+        Composite::from_inline("some `code`")
+    );
+}
+
+#[test]
+fn named_arguments() {
+    let value = String::from("*42*");
+    let composite = mad_inline!(
+        "${name} is ~~$0~~ **${value}**, ${name}! {name} ${not a placeholder}",
+        "none",
+        value = &value,
+        name = "answer",
+    );
+    assert_eq!(
+        composite,
+        Composite::from(vec![
+            Compound::raw_str("answer"),
+            Compound::raw_str(" is "),
+            Compound::raw_str("none").strikeout(),
+            Compound::raw_str(" "),
+            Compound::raw_str("*42*").bold(),
+            Compound::raw_str(", "),
+            Compound::raw_str("answer"),
+            Compound::raw_str("! {name} ${not a placeholder}"),
+        ]),
+    );
+}
+
+#[test]
+fn arguments_in_links() {
+    let composite = mad_inline!("see [the *${what}*](https://dystroy.org)", what = "site");
+    let link = Some(Link::new("https://dystroy.org"));
+    assert_eq!(composite.compounds.len(), 3);
+    assert_eq!(composite.compounds[1].link, link);
+    assert_eq!(composite.compounds[2].as_str(), "site");
+    assert!(composite.compounds[2].italic);
+    assert_eq!(composite.compounds[2].link, link);
+}
+
+#[test]
+fn same_placeholders_as_inline_template() {
+    static MD: &str = "$0$1$2$3$4$5$6$7$8$9 ${a} $10";
+    let values = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "ten"];
+    let composite = mad_inline!(
+        "$0$1$2$3$4$5$6$7$8$9 ${a} $10",
+        values[0],
+        values[1],
+        values[2],
+        values[3],
+        values[4],
+        values[5],
+        values[6],
+        values[7],
+        values[8],
+        values[9],
+        values[10],
+        a = "A",
+    );
+    let template = InlineTemplate::from(MD);
+    let mut expected = template.raw_composite();
+    for (idx, value) in values.iter().enumerate() {
+        template.apply(&mut expected, idx, value);
+    }
+    template.apply_named(&mut expected, "a", "A");
+    assert_eq!(composite, expected);
+    assert_eq!(composite.compounds.last().unwrap().as_str(), "ten");
+}
+
+#[test]
+fn names_with_dashes() {
+    let composite = mad_inline!(
+        "**${file-name}**: ${file-size} {Not-a-name}",
+        "file-name" = "a.txt",
+        "file-size" = "3kB",
+    );
+    let template = InlineTemplate::from("**${file-name}**: ${file-size} {Not-a-name}");
+    let mut expected = template.raw_composite();
+    template.apply_named(&mut expected, "file-name", "a.txt");
+    template.apply_named(&mut expected, "file-size", "3kB");
+    assert_eq!(composite, expected);
+    assert_eq!(composite.compounds[0].as_str(), "a.txt");
+    assert!(composite.compounds[0].bold);
+}
@@ -0,0 +1,269 @@
+//! Functions escaping the strings inserted in markdown, so that their
+//! chars aren't read as styling, following the rules of the parser.
+//!
+//! When the `escaping` feature isn't enabled, the parser doesn't handle
+//! any escaping, so these functions return their input unchanged.
+//!
+//! Underscores are escaped too, so that the escaped strings are read
+//! the same whether the `underscore_emphasis` option is set or not.
+//!
+//! Urls and email addresses, bare or between `<` and `>`, can't be
+//! escaped: they're still made links when the `autolinks` option is
+//! set. Insert them as inline code if they must stay plain text.
+//!
+//! ```
+//! use minimad::*;
+//!
+//! # if escape_inline("*") == "*" { return; } // no escaping feature
+//! let name = "*the* `best` | part";
+//! let md = format!("**{}**", escape_inline(name));
+//! assert_eq!(md, r"**\*the\* \`best\` \| part**");
+//! let composite = parse_inline(&md);
+//! assert!(composite.compounds.iter().all(|c| c.bold));
+//! let content: String = composite.compounds.iter().map(|c| c.as_str()).collect();
+//! assert_eq!(content, name);
+//! ```
+
+use {
+    crate::*,
+    std::borrow::Cow,
+};
+
+/// tell whether the char, after an antislash, is read as itself
+/// instead of having a meaning
+pub(crate) fn is_escapable(c: char) -> bool {
+    matches!(c, '*' | '_' | '~' | '|' | '`' | '[' | ']' | '\\')
+}
+
+/// tell whether the line (or table cell) would be read as a header, a
+/// quote, a list item, a rule or the underline of a header, so that an
+/// antislash before it is read as an escape instead of being kept
+/// (`\# a` is a paragraph "# a" while `\1984` stays as it is)
+pub(crate) fn needs_line_start_escape(line: &str) -> bool {
+    line.starts_with(|c: char| matches!(c, '#' | '>' | '-' | '+' | '=') || c.is_ascii_digit())
+        && (setext_level(line).is_some()
+            || !matches!(
+                Line::from(line),
+                Line::Normal(Composite {
+                    style: CompositeStyle::Paragraph,
+                    ..
+                })
+            ))
+}
+
+fn push_inline_escaped(
+    dst: &mut String,
+    s: &str,
+) {
+    for c in s.chars() {
+        if is_escapable(c) {
+            dst.push('\\');
+        }
+        dst.push(c);
+    }
+}
+
+fn push_line_escaped(
+    dst: &mut String,
+    line: &str,
+) {
+    if needs_line_start_escape(line) {
+        dst.push('\\');
+    }
+    push_inline_escaped(dst, line);
+}
+
+fn needs_inline_escaping(s: &str) -> bool {
+    cfg!(feature = "escaping") && s.contains(is_escapable)
+}
+
+/// escape a string inserted inside a line, so that it doesn't change
+/// the styling (it's still parsed as markdown when at the start of a
+/// line, or in a table, see `escape_md` and `escape_table_cell`)
+pub fn escape_inline(s: &str) -> Cow<'_, str> {
+    if !needs_inline_escaping(s) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    push_inline_escaped(&mut escaped, s);
+    Cow::Owned(escaped)
+}
+
+/// escape a string which may start a line, or be made of several lines,
+/// so that it's read as plain paragraphs.
+///
+/// Leading spaces or tabs, which would make an indented list item or
+/// a code block, can't be escaped and are kept.
+pub fn escape_md(s: &str) -> Cow<'_, str> {
+    if !cfg!(feature = "escaping")
+        || !(s.contains(is_escapable) || s.split('\n').any(needs_line_start_escape))
+    {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    for (idx, line) in s.split('\n').enumerate() {
+        if idx > 0 {
+            escaped.push('\n');
+        }
+        push_line_escaped(&mut escaped, line);
+    }
+    Cow::Owned(escaped)
+}
+
+/// escape a string written just after the `|` starting a table cell,
+/// so that it doesn't end the cell nor makes a list item.
+///
+/// As a table row can't span several lines, newlines are replaced
+/// with spaces.
+pub fn escape_table_cell(s: &str) -> Cow<'_, str> {
+    if !cfg!(feature = "escaping") {
+        return Cow::Borrowed(s);
+    }
+    if !(s.contains(is_escapable) || s.contains('\n') || needs_line_start_escape(s)) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    push_line_escaped(&mut escaped, &s.replace('\n', " "));
+    Cow::Owned(escaped)
+}
+
+fn push_inline_unescaped(
+    dst: &mut String,
+    s: &str,
+) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if is_escapable(next) {
+                    dst.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        dst.push(c);
+    }
+}
+
+/// remove the antislashes which the parser would remove from a string
+/// inside a line (the reverse of `escape_inline`)
+pub fn unescape_inline(s: &str) -> Cow<'_, str> {
+    if !cfg!(feature = "escaping") || !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut unescaped = String::with_capacity(s.len());
+    push_inline_unescaped(&mut unescaped, s);
+    Cow::Owned(unescaped)
+}
+
+/// remove the antislashes which the parser would remove from lines
+/// of text, including the ones at the start of the lines (the reverse
+/// of `escape_md`, and of `escape_table_cell` for a single line)
+pub fn unescape(s: &str) -> Cow<'_, str> {
+    if !cfg!(feature = "escaping") || !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut unescaped = String::with_capacity(s.len());
+    for (idx, line) in s.split('\n').enumerate() {
+        if idx > 0 {
+            unescaped.push('\n');
+        }
+        let line = match line.strip_prefix('\\') {
+            Some(tail) if needs_line_start_escape(tail) => tail,
+            _ => line,
+        };
+        push_inline_unescaped(&mut unescaped, line);
+    }
+    Cow::Owned(unescaped)
+}
+
+#[cfg(all(test, feature = "escaping"))]
+mod tests {
+    use crate::*;
+
+    fn content(composite: &Composite<'_>) -> String {
+        composite.compounds.iter().map(|c| c.as_str()).collect()
+    }
+
+    /// check that the escaped string is parsed back as the original one
+    fn check_line(s: &str) {
+        let escaped = escape_md(s);
+        let line = Line::from(escaped.as_ref());
+        match &line {
+            Line::Normal(composite) => {
+                assert_eq!(composite.style, CompositeStyle::Paragraph, "{:?}", s);
+                assert_eq!(content(composite), s);
+                assert!(
+                    composite.compounds.iter().all(|c| !(c.bold
+                        || c.italic
+                        || c.code
+                        || c.strikeout
+                        || c.link.is_some())),
+                    "{:?}",
+                    s,
+                );
+            }
+            _ => panic!("{:?} gives {:?}", s, line),
+        }
+        assert_eq!(unescape(&escaped), s);
+    }
+
+    #[test]
+    fn escaped_lines() {
+        for s in &[
+            "# not a header",
+            "> not a quote",
+            "- not a list item",
+            "+ nor this one",
+            "* nor this one",
+            "12. not an ordered list item",
+            "1984 was a year",
+            "-not a list item either",
+            "|not|a|table|",
+            "```not a fence",
+            "---",
+            "**not bold** *nor italic* ~~nor struck~~ `nor code`",
+            r"an antislash \ and \\ two, and one at the end \",
+            r"\* escaped",
+            "[not a link](url) ![nor an image](src)",
+            "plain text: nothing to escape",
+        ] {
+            check_line(s);
+        }
+        assert!(matches!(escape_md("plain"), std::borrow::Cow::Borrowed(_)));
+        assert_eq!(escape_md("1984"), "1984");
+        assert_eq!(unescape(r"\1984"), r"\1984");
+        assert_eq!(escape_md("# a\n- b\nc*"), "\\# a\n\\- b\nc\\*");
+        assert_eq!(unescape("\\# a\n\\- b\nc\\*"), "# a\n- b\nc*");
+    }
+
+    #[test]
+    fn escaped_inline_and_cells() {
+        let s = r"*a* | `b` \ c";
+        let md = format!("x {} y", escape_inline(s));
+        assert_eq!(content(&Composite::from_inline(&md)), format!("x {s} y"));
+        assert_eq!(unescape_inline(&escape_inline(s)), s);
+        // underscores are escaped whatever the options of the parser
+        let s = "_a_ __b__";
+        let md = escape_inline(s);
+        let options = Options::default().underscore_emphasis(true);
+        let composite = Composite::from_inline_with_options(&md, options);
+        assert!(composite.compounds.iter().all(|c| !(c.bold || c.italic)));
+        assert_eq!(content(&composite), s);
+        assert_eq!(content(&Composite::from_inline(&md)), s);
+        // a line start isn't escaped inline
+        assert_eq!(escape_inline("# a"), "# a");
+        let cell = escape_table_cell("- a | b\nc");
+        assert_eq!(cell, r"\- a \| b c");
+        let md = format!("|{cell}|d|");
+        match Line::from(md.as_str()) {
+            Line::TableRow(row) => {
+                assert_eq!(row.cells.len(), 2);
+                assert_eq!(row.cells[0].style, CompositeStyle::Paragraph);
+                assert_eq!(content(&row.cells[0]), "- a | b c");
+            }
+            line => panic!("unexpected line: {:?}", line),
+        }
+    }
+}
@@ -0,0 +1,601 @@
+//! Rendering of markdown texts as HTML.
+//!
+//! This module is available with the `html` feature.
+//!
+//! ```
+//! use minimad::*;
+//!
+//! let text = parse_text("# Title\n* some *item*", Options::default());
+//! assert_eq!(
+//!     text.to_html(),
+//!     "<h1>Title</h1>\n<ul>\n<li>some <em>item</em></li>\n</ul>\n",
+//! );
+//! ```
+//!
+//! The links whose url would run a script (`javascript:`, `vbscript:`
+//! or `data:` urls) are rendered as their text only, and the images
+//! whose source could run one as their alt text. Images may be given
+//! as `data:image/` urls, except SVG ones. Apart from that,
+//! urls aren't checked, so the HTML of a text coming from an untrusted
+//! source should still be sanitized before being displayed.
+
+use {
+    crate::*,
+    std::fmt::Write,
+};
+
+/// render a text as HTML
+///
+/// Consecutive list items, quote lines, code lines and table
+/// lines are grouped in lists, blockquotes, code blocks and tables.
+pub fn to_html(text: &Text<'_>) -> String {
+    let mut renderer = Renderer::default();
+    let lines = &text.lines;
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = &lines[idx];
+        if !is_list_part(lines, idx) {
+            renderer.close_lists();
+        }
+        let quote_depth = line.quote_depth();
+        if quote_depth != renderer.quote_depth {
+            renderer.close_lists();
+            renderer.close_code_block();
+            renderer.set_quote_depth(quote_depth);
+        }
+        match line {
+            Line::CodeFence(_) => {
+                if renderer.code_block {
+                    renderer.close_code_block();
+                } else {
+                    let lang = line.code_fence_lang().filter(|lang| !lang.is_empty());
+                    renderer.open_code_block(lang);
+                }
+            }
+            Line::Normal(composite) if composite.is_code() => {
+                if !renderer.code_block {
+                    // the language of a block whose fences weren't kept
+                    renderer.open_code_block(text.code_lang(idx));
+                }
+                for compound in &composite.compounds {
+                    push_escaped(&mut renderer.html, compound.src);
+                }
+                renderer.html.push('\n');
+            }
+            _ => {
+                renderer.close_code_block();
+                match line {
+                    Line::Normal(composite)
+                        if line.is_list_item_continuation() && !renderer.lists.is_empty() =>
+                    {
+                        // after a blank line, it's a new paragraph of the item
+                        renderer.continuation(composite, lines[idx - 1].is_blank());
+                    }
+                    Line::Normal(composite) => renderer.composite(composite),
+                    Line::HorizontalRule => renderer.html.push_str("<hr>\n"),
+                    _ => {
+                        // a table: all the consecutive table rows and rules
+                        let end = lines[idx..]
+                            .iter()
+                            .position(|line| {
+                                !matches!(line, Line::TableRow(_) | Line::TableRule(_))
+                            })
+                            .map_or(lines.len(), |len| idx + len);
+                        renderer.table(&lines[idx..end]);
+                        idx = end;
+                        continue;
+                    }
+                }
+            }
+        }
+        idx += 1;
+    }
+    renderer.close_lists();
+    renderer.close_code_block();
+    renderer.set_quote_depth(0);
+    renderer.html
+}
+
+/// tell whether the line is part of the list being rendered: a list
+/// item, the continuation of an item, or a blank line before a continuation
+fn is_list_part(
+    lines: &[Line<'_>],
+    idx: usize,
+) -> bool {
+    match &lines[idx] {
+        Line::Normal(composite) if composite.is_list_item() => true,
+        line if line.is_blank() => lines[idx..]
+            .iter()
+            .find(|line| !line.is_blank())
+            .is_some_and(Line::is_list_item_continuation),
+        line => line.is_list_item_continuation(),
+    }
+}
+
+impl Text<'_> {
+    /// render the text as HTML
+    pub fn to_html(&self) -> String {
+        to_html(self)
+    }
+}
+
+/// replace the chars having a special meaning in HTML
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    push_escaped(&mut escaped, s);
+    escaped
+}
+
+/// tell whether following the link, or loading the image, could run
+/// a script, ignoring the case and the blanks or control chars which
+/// browsers ignore too
+fn is_script_url(
+    href: &str,
+    image: bool,
+) -> bool {
+    let url: String = href
+        .chars()
+        .filter(|c| !(c.is_whitespace() || c.is_control()))
+        .take(20)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    match url.split_once(':') {
+        Some(("javascript" | "vbscript", _)) => true,
+        // svg images may hold scripts
+        Some(("data", data)) => {
+            !(image && data.starts_with("image/") && !data.starts_with("image/svg"))
+        }
+        _ => false,
+    }
+}
+
+fn push_escaped(
+    html: &mut String,
+    s: &str,
+) {
+    for c in s.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            _ => html.push(c),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Renderer {
+    html: String,
+    lists: Vec<(u8, bool)>, // level and orderedness of the open lists
+    quote_depth: u8,        // number of open blockquotes
+    code_block: bool,
+}
+
+impl Renderer {
+    fn close_lists(&mut self) {
+        while let Some((_, ordered)) = self.lists.pop() {
+            self.html.push_str(if ordered {
+                "</li>\n</ol>\n"
+            } else {
+                "</li>\n</ul>\n"
+            });
+        }
+    }
+    /// open or close blockquotes to reach the depth
+    fn set_quote_depth(
+        &mut self,
+        quote_depth: u8,
+    ) {
+        while self.quote_depth < quote_depth {
+            self.html.push_str("<blockquote>\n");
+            self.quote_depth += 1;
+        }
+        while self.quote_depth > quote_depth {
+            self.html.push_str("</blockquote>\n");
+            self.quote_depth -= 1;
+        }
+    }
+    fn open_code_block(
+        &mut self,
+        lang: Option<&str>,
+    ) {
+        self.html.push_str("<pre><code");
+        if let Some(lang) = lang {
+            self.html.push_str(" class=\"language-");
+            push_escaped(&mut self.html, lang);
+            self.html.push('"');
+        }
+        self.html.push('>');
+        self.code_block = true;
+    }
+    fn close_code_block(&mut self) {
+        if self.code_block {
+            self.html.push_str("</code></pre>\n");
+            self.code_block = false;
+        }
+    }
+    /// close the lists deeper than the level, but not the outermost one,
+    /// so that the item of that level is the open one
+    fn close_deeper_lists(
+        &mut self,
+        level: u8,
+    ) {
+        while self.lists.len() > 1 && matches!(self.lists.last(), Some((l, _)) if *l > level) {
+            let (_, o) = self.lists.pop().unwrap();
+            self.html.push_str(if o {
+                "</li>\n</ol>\n"
+            } else {
+                "</li>\n</ul>\n"
+            });
+        }
+    }
+    /// open the list item, and the lists or close the previous items
+    /// as needed for the level
+    fn list_item(
+        &mut self,
+        level: u8,
+        ordered: Option<u32>,
+    ) {
+        while matches!(self.lists.last(), Some((l, _)) if *l > level) {
+            let (_, o) = self.lists.pop().unwrap();
+            self.html.push_str(if o {
+                "</li>\n</ol>\n"
+            } else {
+                "</li>\n</ul>\n"
+            });
+        }
+        match self.lists.last() {
+            Some(&(l, o)) if l == level && o == ordered.is_some() => {
+                self.html.push_str("</li>\n");
+            }
+            Some(&(l, o)) if l == level => {
+                // same level but another kind of list
+                self.lists.pop();
+                self.html.push_str(if o {
+                    "</li>\n</ol>\n"
+                } else {
+                    "</li>\n</ul>\n"
+                });
+                self.open_list(level, ordered);
+            }
+            _ => {
+                if !self.lists.is_empty() {
+                    self.html.push('\n');
+                }
+                self.open_list(level, ordered);
+            }
+        }
+        self.html.push_str("<li>");
+    }
+    fn open_list(
+        &mut self,
+        level: u8,
+        ordered: Option<u32>,
+    ) {
+        match ordered {
+            Some(1) => self.html.push_str("<ol>\n"),
+            Some(start) => {
+                let _ = writeln!(self.html, "<ol start=\"{start}\">");
+            }
+            None => self.html.push_str("<ul>\n"),
+        }
+        self.lists.push((level, ordered.is_some()));
+    }
+    fn composite(
+        &mut self,
+        composite: &Composite<'_>,
+    ) {
+        match composite.style {
+            CompositeStyle::Paragraph
+            | CompositeStyle::Quote
+            | CompositeStyle::ListItemContinuation(_) => {
+                // the blockquotes are opened with the lines
+                if !composite.is_empty() {
+                    self.html.push_str("<p>");
+                    self.compounds(&composite.compounds);
+                    self.html.push_str("</p>\n");
+                }
+            }
+            CompositeStyle::Header(level) => {
+                let level = level.clamp(1, 6);
+                let _ = write!(self.html, "<h{level}>");
+                self.compounds(&composite.compounds);
+                let _ = writeln!(self.html, "</h{level}>");
+            }
+            CompositeStyle::ListItem(level) => {
+                self.list_item(level, None);
+                self.compounds(&composite.compounds);
+            }
+            CompositeStyle::TaskListItem { level, checked } => {
+                self.list_item(level, None);
+                self.html.push_str(if checked {
+                    r#"<input type="checkbox" checked disabled> "#
+                } else {
+                    r#"<input type="checkbox" disabled> "#
+                });
+                self.compounds(&composite.compounds);
+            }
+            CompositeStyle::OrderedListItem { level, index } => {
+                self.list_item(level, Some(index));
+                self.compounds(&composite.compounds);
+            }
+            CompositeStyle::Code => {} // handled with the lines
+        }
+    }
+    /// render the continuation of the open list item of its level,
+    /// after having closed the deeper lists
+    fn continuation(
+        &mut self,
+        composite: &Composite<'_>,
+        new_paragraph: bool,
+    ) {
+        if let CompositeStyle::ListItemContinuation(level) = composite.style {
+            self.close_deeper_lists(level);
+        }
+        if !self.html.ends_with('\n') {
+            self.html.push('\n');
+        }
+        if new_paragraph {
+            self.html.push_str("<p>");
+            self.compounds(&composite.compounds);
+            self.html.push_str("</p>");
+        } else {
+            self.compounds(&composite.compounds);
+        }
+    }
+    /// render the content of a composite
+    fn compounds(
+        &mut self,
+        compounds: &[Compound<'_>],
+    ) {
+        let mut idx = 0;
+        while idx < compounds.len() {
+            let link = match compounds[idx].link {
+                Some(link) if !link.is_unresolved() => link,
+                _ => {
+                    self.compound(&compounds[idx]);
+                    idx += 1;
+                    continue;
+                }
+            };
+            let end = compounds[idx..]
+                .iter()
+                .position(|c| c.link != Some(link))
+                .map_or(compounds.len(), |len| idx + len);
+            if is_script_url(&link.href(), link.image) {
+                for compound in &compounds[idx..end] {
+                    self.compound(compound);
+                }
+            } else if link.image {
+                self.html.push_str("<img src=\"");
+                push_escaped(&mut self.html, &link.href());
+                self.html.push_str("\" alt=\"");
+                for compound in &compounds[idx..end] {
+                    push_escaped(&mut self.html, compound.src);
+                }
+                self.html.push('"');
+                self.title(link.title);
+                self.html.push('>');
+            } else {
+                self.html.push_str("<a href=\"");
+                push_escaped(&mut self.html, &link.href());
+                self.html.push('"');
+                self.title(link.title);
+                self.html.push('>');
+                for compound in &compounds[idx..end] {
+                    self.compound(compound);
+                }
+                self.html.push_str("</a>");
+            }
+            idx = end;
+        }
+    }
+    fn title(
+        &mut self,
+        title: Option<&str>,
+    ) {
+        if let Some(title) = title {
+            self.html.push_str(" title=\"");
+            push_escaped(&mut self.html, title);
+            self.html.push('"');
+        }
+    }
+    fn compound(
+        &mut self,
+        compound: &Compound<'_>,
+    ) {
+        let tags = [
+            (compound.bold, "strong"),
+            (compound.italic, "em"),
+            (compound.strikeout, "del"),
+            (compound.code, "code"),
+        ];
+        for (_, tag) in tags.iter().filter(|(on, _)| *on) {
+            let _ = write!(self.html, "<{tag}>");
+        }
+        push_escaped(&mut self.html, compound.src);
+        for (_, tag) in tags.iter().rev().filter(|(on, _)| *on) {
+            let _ = write!(self.html, "</{tag}>");
+        }
+    }
+    /// render consecutive table rows and rules
+    ///
+    /// The rows before the first rule, if any, are header rows. The
+    /// alignments of the cells are the ones of the first rule.
+    fn table(
+        &mut self,
+        lines: &[Line<'_>],
+    ) {
+        let rule_idx = lines
+            .iter()
+            .position(|line| matches!(line, Line::TableRule(_)));
+        let aligns: &[Alignment] = match rule_idx.map(|idx| &lines[idx]) {
+            Some(Line::TableRule(rule)) => &rule.cells,
+            _ => &[],
+        };
+        self.html.push_str("<table>\n");
+        let mut in_head = rule_idx.is_some_and(|idx| idx > 0);
+        self.html
+            .push_str(if in_head { "<thead>\n" } else { "<tbody>\n" });
+        for (idx, line) in lines.iter().enumerate() {
+            let row = match line {
+                Line::TableRow(row) => row,
+                _ => {
+                    if in_head && Some(idx) == rule_idx {
+                        self.html.push_str("</thead>\n<tbody>\n");
+                        in_head = false;
+                    }
+                    continue;
+                }
+            };
+            let tag = if in_head { "th" } else { "td" };
+            self.html.push_str("<tr>");
+            for (cell_idx, cell) in row.cells.iter().enumerate() {
+                let align = match aligns.get(cell_idx) {
+                    Some(Alignment::Left) => " style=\"text-align:left\"",
+                    Some(Alignment::Center) => " style=\"text-align:center\"",
+                    Some(Alignment::Right) => " style=\"text-align:right\"",
+                    _ => "",
+                };
+                let _ = write!(self.html, "<{tag}{align}>");
+                self.compounds(&cell.compounds);
+                let _ = write!(self.html, "</{tag}>");
+            }
+            self.html.push_str("</tr>\n");
+        }
+        self.html
+            .push_str(if in_head { "</thead>\n" } else { "</tbody>\n" });
+        self.html.push_str("</table>\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(
+            parse_text("a <b> & 'c'", Options::default()).to_html(),
+            "<p>a &lt;b&gt; &amp; &#39;c&#39;</p>\n",
+        );
+    }
+
+    #[test]
+    fn nested_lists() {
+        let md = "* a\n  * b\n  1. c\n  2. d\n* **e**\n3. f";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<ul>\n<li>a\n\
+            <ul>\n<li>b</li>\n</ul>\n\
+            <ol>\n<li>c</li>\n<li>d</li>\n</ol>\n\
+            </li>\n<li><strong>e</strong></li>\n</ul>\n\
+            <ol start=\"3\">\n<li>f</li>\n</ol>\n",
+        );
+    }
+
+    #[test]
+    fn nested_quotes() {
+        let md = "> a\n>> b\n>> * c\n> # d\ne";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<blockquote>\n<p>a</p>\n\
+            <blockquote>\n<p>b</p>\n<ul>\n<li>c</li>\n</ul>\n</blockquote>\n\
+            <h1>d</h1>\n</blockquote>\n<p>e</p>\n",
+        );
+    }
+
+    #[test]
+    fn list_item_continuations() {
+        let md = "* a\nlazy\n\n  *more*\n* b\n\nc";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<ul>\n<li>a\nlazy\n<p><em>more</em></p></li>\n<li>b</li>\n</ul>\n<p>c</p>\n",
+        );
+    }
+
+    #[test]
+    fn continuations_after_nested_lists() {
+        let md = "* a\n  * b\n\n  back to a";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<ul>\n<li>a\n<ul>\n<li>b</li>\n</ul>\n<p>back to a</p></li>\n</ul>\n",
+        );
+        let md = "1. a\n   * b\n\n   back to a\n2. c";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<ol>\n<li>a\n<ul>\n<li>b</li>\n</ul>\n<p>back to a</p></li>\n<li>c</li>\n</ol>\n",
+        );
+    }
+
+    #[test]
+    fn task_lists() {
+        let md = "* [x] done\n* [ ] *to do*";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<ul>\n<li><input type=\"checkbox\" checked disabled> done</li>\n\
+            <li><input type=\"checkbox\" disabled> <em>to do</em></li>\n</ul>\n",
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let md = r#"## Code
+```rust
+let a = b < c;
+```
+    indented
+> a [link](https://dystroy.org "the site")
+> ![img](i.png)
+---"#;
+        assert_eq!(
+            parse_text(md, Options::default().keep_code_fences(true)).to_html(),
+            "<h2>Code</h2>\n\
+            <pre><code class=\"language-rust\">let a = b &lt; c;\n</code></pre>\n\
+            <pre><code>indented\n</code></pre>\n\
+            <blockquote>\n\
+            <p>a <a href=\"https://dystroy.org\" title=\"the site\">link</a></p>\n\
+            <p><img src=\"i.png\" alt=\"img\"></p>\n\
+            </blockquote>\n\
+            <hr>\n",
+        );
+        // the language is kept even when the fences aren't
+        let md = "```rust\nlet a = 1;\n```\n> ```\n> b\n> ```";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<pre><code class=\"language-rust\">let a = 1;\n</code></pre>\n\
+            <blockquote>\n<pre><code>b\n</code></pre>\n</blockquote>\n",
+        );
+    }
+
+    #[test]
+    fn script_links() {
+        let md = "[a](javascript:alert(1)) [b](<JavaScript :x>) [c](vbscript:x) \
+            [d](data:text/html,x) [e](https://dystroy.org/data:x) ![f](data:image/png,x)";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<p>a b c d <a href=\"https://dystroy.org/data:x\">e</a> \
+            <img src=\"data:image/png,x\" alt=\"f\"></p>\n",
+        );
+        let md = "![a](data:text/html,x) ![b](<Data: image/svg+xml,x>) ![c](javascript:x) \
+            ![d](data:image/gif,x)";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<p>a b c <img src=\"data:image/gif,x\" alt=\"d\"></p>\n",
+        );
+    }
+
+    #[test]
+    fn table() {
+        let md = "|a|b|\n|:-|-:|\n|*c*|`d`|";
+        assert_eq!(
+            parse_text(md, Options::default()).to_html(),
+            "<table>\n<thead>\n<tr><th style=\"text-align:left\">a</th><th style=\"text-align:right\">b</th></tr>\n\
+            </thead>\n<tbody>\n\
+            <tr><td style=\"text-align:left\"><em>c</em></td><td style=\"text-align:right\"><code>d</code></td></tr>\n\
+            </tbody>\n</table>\n",
+        );
+    }
+}
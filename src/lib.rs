@@ -53,12 +53,16 @@ Note that Termimad contains macros and tools to deal with templates. If your goa
 */
 
 pub mod clean;
+pub mod escape;
+#[cfg(feature = "html")]
+pub mod html;
 mod markdown;
 pub mod parser;
 mod template;
 
 pub use {
     clean::*,
+    escape::*,
     markdown::*,
     parser::Options,
     template::*,
@@ -8,6 +8,12 @@ pub enum CompositeStyle {
     Paragraph,
     Header(u8),   // never 0, and <= MAX_HEADER_DEPTH
     ListItem(u8), // can't be built > 3 by parsing: *, -, +
+    TaskListItem {
+        // can't be built > 3 by parsing: - [ ], - [x]
+        level: u8,
+        checked: bool,
+    },
+    ListItemContinuation(u8), // more text of the list item of this level
     OrderedListItem {
         // can't be built > 3 by parsing: 1., 1)
         level: u8,
@@ -21,10 +27,15 @@ pub enum CompositeStyle {
 /// It's defined by
 /// - the global style of the composite, if any
 /// - a vector of styled parts
+/// - the number of quotes it's in, if any
+///
+/// Inside quotes, list items, headers and code keep their style
+/// while other lines have the `Quote` style.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Composite<'a> {
     pub style: CompositeStyle,
     pub compounds: Vec<Compound<'a>>,
+    pub quote_depth: u8, // 0 when not in a quote, 2 for `>> quoted`
 }
 
 impl<'a> From<Vec<Compound<'a>>> for Composite<'a> {
@@ -32,6 +43,7 @@ impl<'a> From<Vec<Compound<'a>>> for Composite<'a> {
         Composite {
             style: CompositeStyle::Paragraph,
             compounds,
+            quote_depth: 0,
         }
     }
 }
@@ -41,6 +53,7 @@ impl Default for Composite<'_> {
         Self {
             style: CompositeStyle::Paragraph,
             compounds: Vec::new(),
+            quote_depth: 0,
         }
     }
 }
@@ -53,10 +66,19 @@ impl<'a> Composite<'a> {
     pub fn from_inline(md: &'a str) -> Composite<'a> {
         parser::LineParser::from(md).inline()
     }
+    /// parse a monoline markdown snippet with specific options
+    /// (only the ones relevant to a single line are applied).
+    pub fn from_inline_with_options(
+        md: &'a str,
+        options: Options,
+    ) -> Composite<'a> {
+        parser::LineParser::from(md).options(options).inline()
+    }
     pub fn raw_str(s: &'a str) -> Composite<'a> {
         Self {
             style: CompositeStyle::Paragraph,
             compounds: vec![Compound::raw_str(s)],
+            quote_depth: 0,
         }
     }
     pub fn is_code(&self) -> bool {
@@ -65,12 +87,66 @@ impl<'a> Composite<'a> {
     pub fn is_list_item(&self) -> bool {
         matches!(
             self.style,
-            CompositeStyle::ListItem(_) | CompositeStyle::OrderedListItem { .. }
+            CompositeStyle::ListItem(_)
+                | CompositeStyle::TaskListItem { .. }
+                | CompositeStyle::OrderedListItem { .. }
         )
     }
+    /// return whether the task is checked, if the composite is
+    /// a task list item
+    pub fn checked(&self) -> Option<bool> {
+        match self.style {
+            CompositeStyle::TaskListItem { checked, .. } => Some(checked),
+            _ => None,
+        }
+    }
+    /// check or uncheck the task, if the composite is a task list
+    /// item, and return whether it was one
+    pub fn set_checked(
+        &mut self,
+        value: bool,
+    ) -> bool {
+        match &mut self.style {
+            CompositeStyle::TaskListItem { checked, .. } => {
+                *checked = value;
+                true
+            }
+            _ => false,
+        }
+    }
+    /// check the task if it was unchecked, and conversely, returning
+    /// the new state, if the composite is a task list item
+    pub fn toggle_checked(&mut self) -> Option<bool> {
+        let checked = !self.checked()?;
+        self.set_checked(checked);
+        Some(checked)
+    }
+    /// tell whether the composite has the `Quote` style, which is the
+    /// style of the paragraphs in quotes
     pub fn is_quote(&self) -> bool {
         matches!(self.style, CompositeStyle::Quote)
     }
+    /// tell whether the composite is in a quote, whatever its style
+    pub fn is_quoted(&self) -> bool {
+        self.quote_depth > 0 || self.is_quote()
+    }
+    /// return the links and images of the composite, in order.
+    ///
+    /// A link whose text is made of several compounds (because
+    /// of styling) is returned only once.
+    pub fn links(&self) -> Vec<Link<'a>> {
+        let mut links: Vec<Link<'a>> = Vec::new();
+        let mut previous = None;
+        for compound in &self.compounds {
+            if let Some(link) = compound.link {
+                if previous != Some(link) {
+                    links.push(link);
+                }
+            }
+            previous = compound.link;
+        }
+        links
+    }
     /// return the total number of characters in the composite
     ///
     /// Example
@@ -96,9 +172,9 @@ impl<'a> Composite<'a> {
             if self.compounds[0].code {
                 break;
             }
-            self.compounds[0].src = self.compounds[0]
-                .src
-                .trim_start_matches(char::is_whitespace);
+            let src = self.compounds[0].src;
+            let trimmed_len = src.len() - src.trim_start_matches(char::is_whitespace).len();
+            self.compounds[0] = self.compounds[0].tail(trimmed_len);
             if self.compounds[0].is_empty() {
                 self.compounds.remove(0);
             } else {
@@ -117,9 +193,11 @@ impl<'a> Composite<'a> {
             if self.compounds[last].code {
                 break;
             }
-            self.compounds[last].src = self.compounds[last]
+            let kept_len = self.compounds[last]
                 .src
-                .trim_end_matches(char::is_whitespace);
+                .trim_end_matches(char::is_whitespace)
+                .len();
+            self.compounds[last] = self.compounds[last].sub(0, kept_len);
             if self.compounds[last].is_empty() {
                 self.compounds.remove(last);
             } else {
@@ -134,6 +212,17 @@ impl<'a> Composite<'a> {
     pub fn is_empty(&self) -> bool {
         self.compounds.len() == 0
     }
+    /// return the span covering the compounds of the composite, if
+    /// they come from parsed markdown.
+    ///
+    /// The span doesn't include the markers which aren't part of the
+    /// compounds, like the `# ` of a header.
+    pub fn span(&self) -> Option<Span> {
+        self.compounds
+            .iter()
+            .filter_map(|compound| compound.span)
+            .reduce(Span::union)
+    }
     /// remove characters, and whole compounds if necessary
     pub fn remove_chars_left(
         &mut self,
@@ -214,7 +303,8 @@ mod tests {
                 compounds: vec![
                     Compound::raw_str("some").italic(),
                     Compound::raw_str(" text"),
-                ]
+                ],
+                quote_depth: 0,
             }
         );
     }
@@ -227,7 +317,8 @@ mod tests {
             left,
             Composite {
                 style: CompositeStyle::Paragraph,
-                compounds: vec![Compound::raw_str(" ").code(),]
+                compounds: vec![Compound::raw_str(" ").code(),],
+                quote_depth: 0,
             }
         );
     }
@@ -254,7 +345,8 @@ mod tests {
                     Compound::raw_str("est").code(),
                     Compound::raw_str(" "),
                     Compound::raw_str("7").italic(),
-                ]
+                ],
+                quote_depth: 0,
             },
         );
         composite.remove_chars_left(8);
@@ -270,8 +362,24 @@ mod tests {
                     Compound::raw_str(" "),
                     Compound::raw_str("est ").italic(),
                     Compound::raw_str("rou").italic().bold(),
-                ]
+                ],
+                quote_depth: 0,
             },
         );
     }
+
+    #[test]
+    fn composite_links() {
+        let composite = Composite::from_inline(
+            "see [the **doc**](https://dystroy.org) or ![logo](logo.png) and [the **doc**](https://dystroy.org)",
+        );
+        assert_eq!(
+            composite.links(),
+            vec![
+                Link::new("https://dystroy.org"),
+                Link::new_image("logo.png"),
+                Link::new("https://dystroy.org"),
+            ]
+        );
+    }
 }
@@ -1,17 +1,34 @@
-use std::fmt::{
-    self,
-    Write,
+use {
+    crate::{
+        Link,
+        Span,
+    },
+    std::{
+        fmt::{
+            self,
+            Write,
+        },
+        hash::{
+            Hash,
+            Hasher,
+        },
+    },
 };
 
 /// a Compound is a part of a line with a consistent styling.
 /// It can be part of word, several words, some inline code, or even the whole line.
-#[derive(Clone, PartialEq, Eq, Hash)]
+///
+/// Two compounds are equal when they have the same content and
+/// styling, wherever they come from: the span isn't compared.
+#[derive(Clone)]
 pub struct Compound<'s> {
     pub src: &'s str,
     pub bold: bool,
     pub italic: bool,
     pub code: bool,
     pub strikeout: bool,
+    pub link: Option<Link<'s>>, // when part of the text of a link or image
+    pub span: Option<Span>,     // position in the parsed source, if any
 }
 
 impl<'s> Compound<'s> {
@@ -24,14 +41,20 @@ impl<'s> Compound<'s> {
             italic: false,
             code: false,
             strikeout: false,
+            link: None,
+            span: None,
         }
     }
     /// change the content but keeps the style arguments
+    ///
+    /// The compound loses its span as the content doesn't come
+    /// from the source anymore.
     pub fn set_str(
         &mut self,
         src: &'s str,
     ) {
         self.src = src;
+        self.span = None;
     }
     /// change the attributes by taking the values from the other
     /// compound, keeping the str
@@ -58,6 +81,8 @@ impl<'s> Compound<'s> {
             italic: self.italic,
             code: self.code,
             strikeout: self.strikeout,
+            link: self.link,
+            span: self.span.map(|span| span.sub(r_start, r_end)),
         }
     }
     /// return a sub part of the compound, with the same styling
@@ -100,6 +125,8 @@ impl<'s> Compound<'s> {
             italic: self.italic,
             code: self.code,
             strikeout: self.strikeout,
+            link: self.link,
+            span: self.span.map(|span| span.sub(r_start, span.len())),
         }
     }
     /// return a sub part at end of the compound, with the same styling
@@ -129,14 +156,9 @@ impl<'s> Compound<'s> {
         tail_size: usize,
     ) -> Compound<'s> {
         let cut = self.src.len() - tail_size;
-        let tail = Compound {
-            src: &self.src[cut..],
-            bold: self.bold,
-            italic: self.italic,
-            code: self.code,
-            strikeout: self.strikeout,
-        };
+        let tail = self.tail(cut);
         self.src = &self.src[0..cut];
+        self.span = self.span.map(|span| span.sub(0, cut));
         tail
     }
 
@@ -153,6 +175,8 @@ impl<'s> Compound<'s> {
             italic: false,
             code: false,
             strikeout: false,
+            link: None,
+            span: Some(Span::new(0, start, end)),
         }
     }
     pub fn new(
@@ -170,6 +194,8 @@ impl<'s> Compound<'s> {
             bold,
             code,
             strikeout,
+            link: None,
+            span: Some(Span::new(0, start, end)),
         }
     }
     pub fn bold(mut self) -> Compound<'s> {
@@ -188,6 +214,13 @@ impl<'s> Compound<'s> {
         self.strikeout = true;
         self
     }
+    pub fn link(
+        mut self,
+        link: Link<'s>,
+    ) -> Compound<'s> {
+        self.link = Some(link);
+        self
+    }
     pub fn set_bold(
         &mut self,
         bold: bool,
@@ -212,6 +245,15 @@ impl<'s> Compound<'s> {
     ) {
         self.strikeout = strikeout;
     }
+    pub fn set_link(
+        &mut self,
+        link: Option<Link<'s>>,
+    ) {
+        self.link = link;
+    }
+    pub fn is_link(&self) -> bool {
+        self.link.is_some()
+    }
     pub fn as_str(&self) -> &'s str {
         self.src
     }
@@ -223,6 +265,40 @@ impl<'s> Compound<'s> {
     }
 }
 
+impl PartialEq for Compound<'_> {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.src == other.src
+            && self.bold == other.bold
+            && self.italic == other.italic
+            && self.code == other.code
+            && self.strikeout == other.strikeout
+            && self.link == other.link
+    }
+}
+
+impl Eq for Compound<'_> {}
+
+impl Hash for Compound<'_> {
+    fn hash<H: Hasher>(
+        &self,
+        state: &mut H,
+    ) {
+        self.src.hash(state);
+        self.bold.hash(state);
+        self.italic.hash(state);
+        self.code.hash(state);
+        self.strikeout.hash(state);
+        self.link.hash(state);
+    }
+}
+
+/// Write the raw content of the compound, without its style
+/// markers, as when it's displayed by itself in a terminal.
+///
+/// Use `Compound::to_markdown` to get the markdown of the compound.
 impl fmt::Display for Compound<'_> {
     fn fmt(
         &self,
@@ -253,6 +329,28 @@ impl fmt::Debug for Compound<'_> {
         f.write_char('"')?;
         f.write_str(self.as_str())?;
         f.write_char('"')?;
+        if let Some(link) = &self.link {
+            if link.image {
+                f.write_char('!')?;
+            }
+            write!(f, "({})", link.url)?;
+        }
         Ok(())
     }
 }
+
+#[test]
+fn spans_follow_cuts() {
+    let src = "some ~~striked~~ text";
+    let mut compound = Compound::new(src, 7, 14, false, false, false, true);
+    assert_eq!(compound.span, Some(Span::new(0, 7, 14)));
+    assert_eq!(compound.sub(1, 3).span, Some(Span::new(0, 8, 10)));
+    assert_eq!(compound.tail(2).span, Some(Span::new(0, 9, 14)));
+    let tail = compound.cut_tail(3);
+    assert_eq!(&src[tail.span.unwrap().range()], "ked");
+    assert_eq!(&src[compound.span.unwrap().range()], "stri");
+    compound.set_str("other");
+    assert_eq!(compound.span, None);
+    // the span isn't part of the identity of a compound
+    assert_eq!(tail, Compound::raw_str("ked").strikeout());
+}
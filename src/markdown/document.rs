@@ -0,0 +1,498 @@
+use crate::*;
+
+/// a tree view of a text, with sections under headers, nested
+/// lists, and the lines of tables, code blocks and quotes grouped
+/// in blocks.
+///
+/// The blocks borrow the lines of the text, and the document can be
+/// flattened back into those lines.
+///
+/// ```
+/// use minimad::*;
+///
+/// let text = parse_text("# A\n* a\n  * b\n## B\n# C", Options::default());
+/// let doc = Document::from(&text);
+/// assert_eq!(doc.blocks.len(), 2); // the 2 level 1 sections
+/// assert_eq!(doc.toc(), vec![(1, &text.lines[0]), (2, &text.lines[3]), (1, &text.lines[4])]);
+/// assert_eq!(doc.lines().len(), text.lines.len());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Document<'a> {
+    pub blocks: Vec<Block<'a>>,
+    /// the languages of the code blocks whose fences weren't kept,
+    /// as in the text
+    pub code_langs: Vec<(usize, &'a str)>,
+}
+
+/// a node of a [`Document`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block<'a> {
+    /// a header with the blocks following it, until a header
+    /// of the same level or less deep
+    Section {
+        header: &'a Line<'a>,
+        level: u8,
+        blocks: Vec<Block<'a>>,
+    },
+    /// a normal line, possibly empty
+    Paragraph(&'a Line<'a>),
+    /// consecutive list items, whatever their kind, with their continuations
+    List(Vec<ListItem<'a>>),
+    /// consecutive table rows and rules
+    Table(Vec<&'a Line<'a>>),
+    /// consecutive lines of code, with their fences if they were kept
+    Code(Vec<&'a Line<'a>>),
+    /// consecutive quoted lines, including list items, headers or code
+    Quote(Vec<&'a Line<'a>>),
+    HorizontalRule(&'a Line<'a>),
+}
+
+/// a list item with the deeper items following it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem<'a> {
+    pub line: &'a Line<'a>,
+    /// the lines continuing the text of the item, and the blank
+    /// lines between them
+    pub continuation: Vec<&'a Line<'a>>,
+    pub children: Vec<ListItem<'a>>,
+    /// for each line of the continuation, the number of children
+    /// before it (which isn't 0 when the item is continued after
+    /// a nested list)
+    pub continuation_positions: Vec<usize>,
+}
+
+impl<'a> From<&'a Text<'a>> for Document<'a> {
+    fn from(text: &'a Text<'a>) -> Self {
+        let mut idx = 0;
+        Self {
+            blocks: read_blocks(&text.lines, &mut idx, 0),
+            code_langs: text.code_langs.clone(),
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    /// return the lines of the document, in the order of the text
+    pub fn lines(&self) -> Vec<&'a Line<'a>> {
+        let mut lines = Vec::new();
+        for block in &self.blocks {
+            block.push_lines(&mut lines);
+        }
+        lines
+    }
+    /// build back a flat text
+    pub fn to_text(&self) -> Text<'a> {
+        Text {
+            lines: self.lines().into_iter().cloned().collect(),
+            code_langs: self.code_langs.clone(),
+        }
+    }
+    /// return the level and line of all the headers, in order
+    pub fn toc(&self) -> Vec<(u8, &'a Line<'a>)> {
+        let mut toc = Vec::new();
+        push_headers(&self.blocks, &mut toc);
+        toc
+    }
+}
+
+impl<'a> Block<'a> {
+    fn push_lines(
+        &self,
+        lines: &mut Vec<&'a Line<'a>>,
+    ) {
+        match self {
+            Block::Section { header, blocks, .. } => {
+                lines.push(header);
+                for block in blocks {
+                    block.push_lines(lines);
+                }
+            }
+            Block::Paragraph(line) | Block::HorizontalRule(line) => lines.push(line),
+            Block::List(items) => {
+                for item in items {
+                    item.push_lines(lines);
+                }
+            }
+            Block::Table(block_lines) | Block::Code(block_lines) | Block::Quote(block_lines) => {
+                lines.extend(block_lines);
+            }
+        }
+    }
+    /// return the lines of the block, including the ones of
+    /// its children
+    pub fn lines(&self) -> Vec<&'a Line<'a>> {
+        let mut lines = Vec::new();
+        self.push_lines(&mut lines);
+        lines
+    }
+    /// return the number of lines of the block, which is the number
+    /// of lines hidden when folding it
+    pub fn line_count(&self) -> usize {
+        self.lines().len()
+    }
+}
+
+impl<'a> ListItem<'a> {
+    fn push_lines(
+        &self,
+        lines: &mut Vec<&'a Line<'a>>,
+    ) {
+        lines.push(self.line);
+        let mut continuation = self.continuation.iter().zip(&self.continuation_positions);
+        let mut next = continuation.next();
+        for (idx, child) in self.children.iter().enumerate() {
+            while let Some((line, _)) = next.filter(|(_, &position)| position <= idx) {
+                lines.push(line);
+                next = continuation.next();
+            }
+            child.push_lines(lines);
+        }
+        while let Some((line, _)) = next {
+            lines.push(line);
+            next = continuation.next();
+        }
+    }
+}
+
+fn push_headers<'a>(
+    blocks: &[Block<'a>],
+    toc: &mut Vec<(u8, &'a Line<'a>)>,
+) {
+    for block in blocks {
+        if let Block::Section {
+            header,
+            level,
+            blocks,
+        } = block
+        {
+            toc.push((*level, header));
+            push_headers(blocks, toc);
+        }
+    }
+}
+
+fn list_level(line: &Line<'_>) -> Option<u8> {
+    match line {
+        Line::Normal(Composite {
+            style: CompositeStyle::ListItem(level),
+            ..
+        })
+        | Line::Normal(Composite {
+            style: CompositeStyle::TaskListItem { level, .. },
+            ..
+        })
+        | Line::Normal(Composite {
+            style: CompositeStyle::OrderedListItem { level, .. },
+            ..
+        }) => Some(*level),
+        _ => None,
+    }
+}
+
+/// read the blocks starting at `idx`, until a header whose level
+/// is `section_level` or less
+fn read_blocks<'a>(
+    lines: &'a [Line<'a>],
+    idx: &mut usize,
+    section_level: u8,
+) -> Vec<Block<'a>> {
+    let mut blocks = Vec::new();
+    while *idx < lines.len() {
+        let line = &lines[*idx];
+        let start = *idx;
+        *idx += 1;
+        let block = match line {
+            _ if line.quote_depth() > 0 => {
+                // quoted lines, whatever their kind, make a single block
+                while lines.get(*idx).is_some_and(|line| line.quote_depth() > 0) {
+                    *idx += 1;
+                }
+                Block::Quote(lines[start..*idx].iter().collect())
+            }
+            Line::Normal(Composite {
+                style: CompositeStyle::Header(level),
+                ..
+            }) => {
+                if *level <= section_level {
+                    *idx = start;
+                    break;
+                }
+                Block::Section {
+                    header: line,
+                    level: *level,
+                    blocks: read_blocks(lines, idx, *level),
+                }
+            }
+            Line::Normal(composite) if composite.is_list_item() => {
+                while let Some(len) = list_part_len(&lines[*idx..]) {
+                    *idx += len;
+                }
+                let list_lines = &lines[start..*idx];
+                let mut item_idx = 0;
+                let mut items = Vec::new();
+                while item_idx < list_lines.len() {
+                    items.extend(list_items(list_lines, &mut item_idx, None));
+                    if item_idx < list_lines.len() {
+                        // not an item: a continuation before any item
+                        item_idx += 1;
+                    }
+                }
+                Block::List(items)
+            }
+            Line::Normal(composite) if composite.is_code() => {
+                while *idx < lines.len() && lines[*idx].is_code() {
+                    *idx += 1;
+                }
+                Block::Code(lines[start..*idx].iter().collect())
+            }
+            Line::CodeFence(_) => {
+                // the block goes to the closing fence, if any
+                while *idx < lines.len() && lines[*idx].is_code() {
+                    *idx += 1;
+                }
+                if matches!(lines.get(*idx), Some(Line::CodeFence(_))) {
+                    *idx += 1;
+                }
+                Block::Code(lines[start..*idx].iter().collect())
+            }
+            Line::Normal(_) => Block::Paragraph(line),
+            Line::TableRow(_) | Line::TableRule(_) => {
+                while matches!(
+                    lines.get(*idx),
+                    Some(Line::TableRow(_) | Line::TableRule(_))
+                ) {
+                    *idx += 1;
+                }
+                Block::Table(lines[start..*idx].iter().collect())
+            }
+            Line::HorizontalRule => Block::HorizontalRule(line),
+        };
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// if the lines start with a part of a list (a list item, a continuation,
+/// or blank lines followed by a continuation), return its number of lines
+fn list_part_len(lines: &[Line<'_>]) -> Option<usize> {
+    let blank_count = lines.iter().take_while(|line| line.is_blank()).count();
+    let line = lines.get(blank_count)?;
+    if (blank_count == 0 && list_level(line).is_some()) || line.is_list_item_continuation() {
+        Some(blank_count + 1)
+    } else {
+        None
+    }
+}
+
+/// read the tree of consecutive list items deeper than the parent
+/// level: each item gets as children the deeper items following it
+fn list_items<'a>(
+    lines: &'a [Line<'a>],
+    idx: &mut usize,
+    parent_level: Option<u8>,
+) -> Vec<ListItem<'a>> {
+    let mut items = Vec::new();
+    while let Some(level) = lines.get(*idx).and_then(list_level) {
+        if parent_level.is_some_and(|parent_level| level <= parent_level) {
+            break;
+        }
+        items.push(list_item(lines, idx, parent_level, level));
+    }
+    items
+}
+
+/// read the list item at `idx`, with its continuation and its children.
+///
+/// The continuations of the level of an ancestor (or of a lower level)
+/// end the item, while the other ones are given to the innermost item
+/// which isn't deeper.
+fn list_item<'a>(
+    lines: &'a [Line<'a>],
+    idx: &mut usize,
+    parent_level: Option<u8>,
+    level: u8,
+) -> ListItem<'a> {
+    let mut item = ListItem {
+        line: &lines[*idx],
+        continuation: Vec::new(),
+        children: Vec::new(),
+        continuation_positions: Vec::new(),
+    };
+    *idx += 1;
+    loop {
+        let next = *idx
+            + lines[*idx..]
+                .iter()
+                .take_while(|line| line.is_blank())
+                .count();
+        let line = match lines.get(next) {
+            Some(line) => line,
+            None => break,
+        };
+        if let Some(item_level) = list_level(line) {
+            if item_level <= level {
+                break;
+            }
+            item.children.extend(list_items(lines, idx, Some(level)));
+            continue;
+        }
+        match line {
+            Line::Normal(Composite {
+                style: CompositeStyle::ListItemContinuation(continuation_level),
+                ..
+            }) if parent_level.is_none_or(|parent_level| *continuation_level > parent_level) => {
+                for line in &lines[*idx..=next] {
+                    item.continuation.push(line);
+                    item.continuation_positions.push(item.children.len());
+                }
+                *idx = next + 1;
+            }
+            _ => break,
+        }
+    }
+    item
+}
+
+impl Text<'_> {
+    /// build a tree view of the text
+    pub fn document(&self) -> Document<'_> {
+        Document::from(self)
+    }
+}
+
+#[test]
+fn document_tree() {
+    let md = r#"intro
+# Title
+* a
+  * a.1
+   * a.1.1
+  1. a.2
+* b
+## Sub
+|a|b|
+|-|-|
+> quote
+> again
+```
+code
+```
+# Other
+---"#;
+    let text = parse_text(md, Options::default().keep_code_fences(true));
+    let doc = Document::from(&text);
+    assert_eq!(doc.blocks.len(), 3);
+    assert_eq!(doc.blocks[0], Block::Paragraph(&text.lines[0]));
+    let l = &text.lines;
+    assert_eq!(
+        doc.blocks[1],
+        Block::Section {
+            header: &l[1],
+            level: 1,
+            blocks: vec![
+                Block::List(vec![
+                    ListItem {
+                        line: &l[2],
+                        continuation: vec![],
+                        continuation_positions: vec![],
+                        children: vec![
+                            ListItem {
+                                line: &l[3],
+                                continuation: vec![],
+                                continuation_positions: vec![],
+                                children: vec![ListItem {
+                                    line: &l[4],
+                                    continuation: vec![],
+                                    continuation_positions: vec![],
+                                    children: vec![],
+                                }],
+                            },
+                            ListItem {
+                                line: &l[5],
+                                continuation: vec![],
+                                continuation_positions: vec![],
+                                children: vec![],
+                            },
+                        ],
+                    },
+                    ListItem {
+                        line: &l[6],
+                        continuation: vec![],
+                        continuation_positions: vec![],
+                        children: vec![],
+                    },
+                ]),
+                Block::Section {
+                    header: &l[7],
+                    level: 2,
+                    blocks: vec![
+                        Block::Table(vec![&l[8], &l[9]]),
+                        Block::Quote(vec![&l[10], &l[11]]),
+                        Block::Code(vec![&l[12], &l[13], &l[14]]),
+                    ],
+                },
+            ],
+        }
+    );
+    assert_eq!(doc.blocks[2].line_count(), 2);
+    assert_eq!(doc.to_text(), text);
+}
+
+#[test]
+fn list_item_continuations() {
+    let md = "* a\n  more a\n\n  again a\n  * b\n\n    more b\n\nafter";
+    let text = parse_text(md, Options::default());
+    let doc = Document::from(&text);
+    let l = &text.lines;
+    assert_eq!(
+        doc.blocks,
+        vec![
+            Block::List(vec![ListItem {
+                line: &l[0],
+                continuation: vec![&l[1], &l[2], &l[3]],
+                children: vec![ListItem {
+                    line: &l[4],
+                    continuation: vec![&l[5], &l[6]],
+                    children: vec![],
+                    continuation_positions: vec![0, 0],
+                }],
+                continuation_positions: vec![0, 0, 0],
+            }]),
+            Block::Paragraph(&l[7]),
+            Block::Paragraph(&l[8]),
+        ]
+    );
+    assert_eq!(doc.lines().len(), text.lines.len());
+}
+
+#[test]
+fn continuations_after_nested_lists() {
+    for md in &[
+        "* a\n  * b\n\n  back to a",
+        "1. a\n   * b\n\n   back to a\n2. c",
+    ] {
+        let text = parse_text(md, Options::default());
+        let doc = Document::from(&text);
+        let l = &text.lines;
+        let items = match &doc.blocks[0] {
+            Block::List(items) => items,
+            block => panic!("not a list: {:?}", block),
+        };
+        assert_eq!(
+            items[0],
+            ListItem {
+                line: &l[0],
+                continuation: vec![&l[2], &l[3]],
+                children: vec![ListItem {
+                    line: &l[1],
+                    continuation: vec![],
+                    children: vec![],
+                    continuation_positions: vec![],
+                }],
+                continuation_positions: vec![1, 1],
+            },
+        );
+        assert_eq!(items.len(), l.len() - 3);
+        assert_eq!(doc.to_text(), text);
+    }
+}
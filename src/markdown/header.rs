@@ -26,6 +26,42 @@ pub fn header_level(src: &str) -> usize {
     0
 }
 
+/// return the content of an ATX header without its optional closing
+/// sequence of '#' (like in `## title ##`), which must be preceded by
+/// a space. The content is kept when it's only made of '#'
+pub(crate) fn strip_closing_hashes(content: &str) -> &str {
+    let trimmed = content.trim_end_matches(' ');
+    let without_hashes = trimmed.trim_end_matches('#');
+    if without_hashes.len() == trimmed.len() || !without_hashes.ends_with(' ') {
+        return content;
+    }
+    let stripped = without_hashes.trim_end_matches(' ');
+    if stripped.is_empty() {
+        content
+    } else {
+        stripped
+    }
+}
+
+/// if the line is the underline of a setext header (`===` for a level 1
+/// header, `---` for a level 2 one), return the level
+pub(crate) fn setext_level(src: &str) -> Option<u8> {
+    let indent = src.len() - src.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let underline = src.trim();
+    if underline.is_empty() {
+        None
+    } else if underline.bytes().all(|b| b == b'=') {
+        Some(1)
+    } else if underline.bytes().all(|b| b == b'-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
 #[test]
 fn header_level_count() {
     assert_eq!(header_level(""), 0);
@@ -43,3 +79,28 @@ fn header_level_count() {
     assert_eq!(header_level("######## a b"), 8);
     assert_eq!(header_level("######### a b"), 0); // too deep
 }
+
+#[test]
+fn closing_hashes() {
+    assert_eq!(strip_closing_hashes("title"), "title");
+    assert_eq!(strip_closing_hashes("title ##"), "title");
+    assert_eq!(strip_closing_hashes("title  #####  "), "title");
+    assert_eq!(strip_closing_hashes("title#"), "title#");
+    assert_eq!(strip_closing_hashes("title \\#"), "title \\#");
+    assert_eq!(strip_closing_hashes("C #"), "C");
+    assert_eq!(strip_closing_hashes("C # #"), "C #");
+    assert_eq!(strip_closing_hashes("#"), "#");
+    assert_eq!(strip_closing_hashes("## ##"), "##");
+}
+
+#[test]
+fn setext_underlines() {
+    assert_eq!(setext_level("==="), Some(1));
+    assert_eq!(setext_level("="), Some(1));
+    assert_eq!(setext_level("  ------  "), Some(2));
+    assert_eq!(setext_level("    ---"), None); // that's code
+    assert_eq!(setext_level("-=-"), None);
+    assert_eq!(setext_level("- -"), None);
+    assert_eq!(setext_level(""), None);
+    assert_eq!(setext_level("   "), None);
+}
@@ -30,30 +30,35 @@ impl<'a> Line<'a> {
         Line::Normal(Composite {
             style: CompositeStyle::Paragraph,
             compounds,
+            quote_depth: 0,
         })
     }
     pub fn empty_code_fence() -> Line<'static> {
         Line::CodeFence(Composite {
             style: CompositeStyle::Paragraph,
             compounds: vec![],
+            quote_depth: 0,
         })
     }
     pub fn new_code_fence(compounds: Vec<Compound<'_>>) -> Line<'_> {
         Line::CodeFence(Composite {
             style: CompositeStyle::Paragraph,
             compounds,
+            quote_depth: 0,
         })
     }
     pub fn new_code(compound: Compound<'_>) -> Line<'_> {
         Line::Normal(Composite {
             style: CompositeStyle::Code,
             compounds: vec![compound],
+            quote_depth: 0,
         })
     }
     pub fn new_quote(compounds: Vec<Compound<'_>>) -> Line<'_> {
         Line::Normal(Composite {
             style: CompositeStyle::Quote,
             compounds,
+            quote_depth: 1,
         })
     }
     pub fn new_list_item(
@@ -63,6 +68,18 @@ impl<'a> Line<'a> {
         Line::Normal(Composite {
             style: CompositeStyle::ListItem(depth),
             compounds,
+            quote_depth: 0,
+        })
+    }
+    pub fn new_task_list_item(
+        level: u8,
+        checked: bool,
+        compounds: Vec<Compound<'_>>,
+    ) -> Line<'_> {
+        Line::Normal(Composite {
+            style: CompositeStyle::TaskListItem { level, checked },
+            compounds,
+            quote_depth: 0,
         })
     }
     pub fn new_ordered_list_item(
@@ -73,6 +90,7 @@ impl<'a> Line<'a> {
         Line::Normal(Composite {
             style: CompositeStyle::OrderedListItem { level, index },
             compounds,
+            quote_depth: 0,
         })
     }
     pub fn new_header(
@@ -82,6 +100,7 @@ impl<'a> Line<'a> {
         Line::Normal(Composite {
             style: CompositeStyle::Header(level),
             compounds,
+            quote_depth: 0,
         })
     }
     pub fn new_table_row(cells: Vec<Composite<'_>>) -> Line<'_> {
@@ -100,6 +119,27 @@ impl<'a> Line<'a> {
             _ => true,
         }
     }
+    /// tell whether the line is a paragraph with no content but spaces
+    pub fn is_blank(&self) -> bool {
+        match self {
+            Line::Normal(composite) => {
+                composite.style == CompositeStyle::Paragraph
+                    && composite.quote_depth == 0
+                    && composite.compounds.iter().all(|c| is_blank(c.src))
+            }
+            _ => false,
+        }
+    }
+    /// tell whether the line is the continuation of a list item
+    pub fn is_list_item_continuation(&self) -> bool {
+        matches!(
+            self,
+            Line::Normal(Composite {
+                style: CompositeStyle::ListItemContinuation(_),
+                ..
+            })
+        )
+    }
     #[inline(always)]
     pub fn is_code(&self) -> bool {
         match self {
@@ -107,6 +147,40 @@ impl<'a> Line<'a> {
             _ => false,
         }
     }
+    /// return the composites of the line: one for normal lines and
+    /// code fences, one per cell for table rows, none for rules
+    pub fn composites(&self) -> &[Composite<'a>] {
+        match self {
+            Line::Normal(composite) | Line::CodeFence(composite) => std::slice::from_ref(composite),
+            Line::TableRow(row) => &row.cells,
+            _ => &[],
+        }
+    }
+    pub fn composites_mut(&mut self) -> &mut [Composite<'a>] {
+        match self {
+            Line::Normal(composite) | Line::CodeFence(composite) => std::slice::from_mut(composite),
+            Line::TableRow(row) => &mut row.cells,
+            _ => &mut [],
+        }
+    }
+    /// return the span of the content of the line in the parsed source.
+    ///
+    /// It's None for lines with no content (like horizontal rules
+    /// or table rules) and for lines which weren't parsed.
+    pub fn span(&self) -> Option<Span> {
+        self.composites()
+            .iter()
+            .filter_map(Composite::span)
+            .reduce(Span::union)
+    }
+    /// return the number of quotes the line is in
+    pub fn quote_depth(&self) -> u8 {
+        match self {
+            Line::Normal(composite) if composite.is_quoted() => composite.quote_depth.max(1),
+            Line::CodeFence(composite) => composite.quote_depth,
+            _ => 0,
+        }
+    }
     pub fn code_fence_lang(&self) -> Option<&str> {
         match self {
             Line::CodeFence(composite) => {
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+
+/// the target of an inline link (`[text](url "title")`) or of
+/// an image (`![alt](src "title")`).
+///
+/// The text of the link, or the alt text of the image, isn't
+/// stored here: it's the content of the compounds carrying
+/// the link.
+///
+/// Reference links (`[text][label]`) get their url and title
+/// when the text is parsed, from the `[label]: url "title"`
+/// definition. The url of a reference which couldn't be
+/// resolved is empty.
+///
+/// Links found in the text with the `autolinks` option are
+/// flagged, as their url may lack a scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Link<'s> {
+    pub url: &'s str,
+    pub title: Option<&'s str>,
+    pub image: bool,
+    pub reference: Option<&'s str>, // the label, for reference links
+    pub autolink: bool,             // found in the text with the `autolinks` option
+}
+
+impl<'s> Link<'s> {
+    /// make a link to the given url, without title
+    pub fn new(url: &'s str) -> Link<'s> {
+        Link {
+            url,
+            title: None,
+            image: false,
+            reference: None,
+            autolink: false,
+        }
+    }
+    /// make an image link with the given source, without title
+    pub fn new_image(url: &'s str) -> Link<'s> {
+        Link {
+            url,
+            title: None,
+            image: true,
+            reference: None,
+            autolink: false,
+        }
+    }
+    pub fn title(
+        mut self,
+        title: &'s str,
+    ) -> Link<'s> {
+        self.title = Some(title);
+        self
+    }
+    pub fn reference(
+        mut self,
+        label: &'s str,
+    ) -> Link<'s> {
+        self.reference = Some(label);
+        self
+    }
+    /// flag the link as found in the text, like a bare url
+    pub fn autolink(mut self) -> Link<'s> {
+        self.autolink = true;
+        self
+    }
+    /// tell whether this is a reference link whose label
+    /// wasn't found in the definitions of the text
+    pub fn is_unresolved(&self) -> bool {
+        self.reference.is_some() && self.url.is_empty()
+    }
+    /// return the url to open when following the link.
+    ///
+    /// It's the url, except for email addresses and `www.` urls
+    /// found with the `autolinks` option, which get a `mailto:`
+    /// or `http://` scheme. The url of other links is kept as is.
+    pub fn href(&self) -> Cow<'s, str> {
+        if !self.autolink {
+            Cow::Borrowed(self.url)
+        } else if self.url.starts_with("www.") {
+            Cow::Owned(format!("http://{}", self.url))
+        } else if self.url.contains('@') && !self.url.contains(':') {
+            Cow::Owned(format!("mailto:{}", self.url))
+        } else {
+            Cow::Borrowed(self.url)
+        }
+    }
+}
+
+#[test]
+fn link_href() {
+    assert_eq!(
+        Link::new("https://dystroy.org").href(),
+        "https://dystroy.org"
+    );
+    assert_eq!(
+        Link::new("www.dystroy.org").autolink().href(),
+        "http://www.dystroy.org"
+    );
+    assert_eq!(
+        Link::new("me@dystroy.org").autolink().href(),
+        "mailto:me@dystroy.org"
+    );
+    assert_eq!(
+        Link::new("mailto:me@dystroy.org").autolink().href(),
+        "mailto:me@dystroy.org"
+    );
+    // explicit destinations are kept
+    assert_eq!(Link::new("www.dystroy.org").href(), "www.dystroy.org");
+    assert_eq!(Link::new("me@dystroy.org").href(), "me@dystroy.org");
+}
@@ -1,11 +1,20 @@
 mod align;
 mod composite;
 mod compound;
+mod document;
 mod header;
 mod line;
+mod link;
+mod owned;
+mod serializer;
+mod span;
 mod tbl;
 mod text;
 
+pub(crate) use header::{
+    setext_level,
+    strip_closing_hashes,
+};
 pub use {
     align::Alignment,
     composite::{
@@ -13,12 +22,28 @@ pub use {
         CompositeStyle,
     },
     compound::Compound,
+    document::{
+        Block,
+        Document,
+        ListItem,
+    },
     header::header_level,
     line::{
         Line,
         MAX_HEADER_DEPTH,
     },
+    link::Link,
+    owned::{
+        OwnedComposite,
+        OwnedCompound,
+        OwnedLine,
+        OwnedLink,
+        OwnedTableRow,
+        OwnedText,
+    },
+    span::Span,
     tbl::{
+        Table,
         TableRow,
         TableRule,
     },
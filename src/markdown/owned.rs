@@ -0,0 +1,379 @@
+//! Owned versions of the markdown structures, not borrowing
+//! the parsed source, so that they can be stored, cached or
+//! sent to another thread.
+//!
+//! They're obtained with `into_owned` and give back a borrowing
+//! structure with `as_text`, `as_line`, `as_composite`, etc.
+
+use {
+    crate::*,
+    std::{
+        fmt,
+        hash::{
+            Hash,
+            Hasher,
+        },
+    },
+};
+
+/// an owned version of a [`Link`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedLink {
+    pub url: String,
+    pub title: Option<String>,
+    pub image: bool,
+    pub reference: Option<String>,
+    pub autolink: bool,
+}
+
+/// an owned version of a [`Compound`]
+///
+/// As for compounds, the span isn't part of the identity.
+#[derive(Clone)]
+pub struct OwnedCompound {
+    pub src: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub strikeout: bool,
+    pub link: Option<OwnedLink>,
+    pub span: Option<Span>,
+}
+
+/// an owned version of a [`Composite`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedComposite {
+    pub style: CompositeStyle,
+    pub compounds: Vec<OwnedCompound>,
+    pub quote_depth: u8,
+}
+
+/// an owned version of a [`TableRow`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedTableRow {
+    pub cells: Vec<OwnedComposite>,
+}
+
+/// an owned version of a [`Line`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedLine {
+    Normal(OwnedComposite),
+    TableRow(OwnedTableRow),
+    TableRule(TableRule),
+    HorizontalRule,
+    CodeFence(OwnedComposite),
+}
+
+/// an owned version of a [`Text`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OwnedText {
+    pub lines: Vec<OwnedLine>,
+    pub code_langs: Vec<(usize, String)>,
+}
+
+impl Link<'_> {
+    pub fn into_owned(self) -> OwnedLink {
+        OwnedLink {
+            url: self.url.to_string(),
+            title: self.title.map(str::to_string),
+            image: self.image,
+            reference: self.reference.map(str::to_string),
+            autolink: self.autolink,
+        }
+    }
+}
+
+impl OwnedLink {
+    pub fn as_link(&self) -> Link<'_> {
+        Link {
+            url: &self.url,
+            title: self.title.as_deref(),
+            image: self.image,
+            reference: self.reference.as_deref(),
+            autolink: self.autolink,
+        }
+    }
+}
+
+impl Compound<'_> {
+    pub fn into_owned(self) -> OwnedCompound {
+        OwnedCompound {
+            src: self.src.to_string(),
+            bold: self.bold,
+            italic: self.italic,
+            code: self.code,
+            strikeout: self.strikeout,
+            link: self.link.map(Link::into_owned),
+            span: self.span,
+        }
+    }
+}
+
+impl OwnedCompound {
+    pub fn as_compound(&self) -> Compound<'_> {
+        Compound {
+            src: &self.src,
+            bold: self.bold,
+            italic: self.italic,
+            code: self.code,
+            strikeout: self.strikeout,
+            link: self.link.as_ref().map(OwnedLink::as_link),
+            span: self.span,
+        }
+    }
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+    pub fn char_length(&self) -> usize {
+        self.src.chars().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.src.is_empty()
+    }
+    pub fn is_link(&self) -> bool {
+        self.link.is_some()
+    }
+}
+
+impl PartialEq for OwnedCompound {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.as_compound() == other.as_compound()
+    }
+}
+
+impl Eq for OwnedCompound {}
+
+impl Hash for OwnedCompound {
+    fn hash<H: Hasher>(
+        &self,
+        state: &mut H,
+    ) {
+        self.as_compound().hash(state);
+    }
+}
+
+impl fmt::Display for OwnedCompound {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt::Display::fmt(&self.as_compound(), f)
+    }
+}
+
+impl fmt::Debug for OwnedCompound {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_compound(), f)
+    }
+}
+
+impl Composite<'_> {
+    pub fn into_owned(self) -> OwnedComposite {
+        OwnedComposite {
+            style: self.style,
+            compounds: self
+                .compounds
+                .into_iter()
+                .map(Compound::into_owned)
+                .collect(),
+            quote_depth: self.quote_depth,
+        }
+    }
+}
+
+impl OwnedComposite {
+    pub fn as_composite(&self) -> Composite<'_> {
+        Composite {
+            style: self.style,
+            compounds: self
+                .compounds
+                .iter()
+                .map(OwnedCompound::as_compound)
+                .collect(),
+            quote_depth: self.quote_depth,
+        }
+    }
+    pub fn is_code(&self) -> bool {
+        matches!(self.style, CompositeStyle::Code)
+    }
+    pub fn is_list_item(&self) -> bool {
+        matches!(
+            self.style,
+            CompositeStyle::ListItem(_)
+                | CompositeStyle::TaskListItem { .. }
+                | CompositeStyle::OrderedListItem { .. }
+        )
+    }
+    pub fn is_quote(&self) -> bool {
+        matches!(self.style, CompositeStyle::Quote)
+    }
+    pub fn is_quoted(&self) -> bool {
+        self.quote_depth > 0 || self.is_quote()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.compounds.is_empty()
+    }
+    pub fn char_length(&self) -> usize {
+        self.compounds.iter().map(OwnedCompound::char_length).sum()
+    }
+    pub fn span(&self) -> Option<Span> {
+        self.as_composite().span()
+    }
+}
+
+impl Line<'_> {
+    pub fn into_owned(self) -> OwnedLine {
+        match self {
+            Line::Normal(composite) => OwnedLine::Normal(composite.into_owned()),
+            Line::TableRow(row) => OwnedLine::TableRow(OwnedTableRow {
+                cells: row.cells.into_iter().map(Composite::into_owned).collect(),
+            }),
+            Line::TableRule(rule) => OwnedLine::TableRule(rule),
+            Line::HorizontalRule => OwnedLine::HorizontalRule,
+            Line::CodeFence(composite) => OwnedLine::CodeFence(composite.into_owned()),
+        }
+    }
+}
+
+impl OwnedLine {
+    pub fn as_line(&self) -> Line<'_> {
+        match self {
+            OwnedLine::Normal(composite) => Line::Normal(composite.as_composite()),
+            OwnedLine::TableRow(row) => Line::TableRow(TableRow {
+                cells: row.cells.iter().map(OwnedComposite::as_composite).collect(),
+            }),
+            OwnedLine::TableRule(rule) => Line::TableRule(rule.clone()),
+            OwnedLine::HorizontalRule => Line::HorizontalRule,
+            OwnedLine::CodeFence(composite) => Line::CodeFence(composite.as_composite()),
+        }
+    }
+    pub fn char_length(&self) -> usize {
+        match self {
+            OwnedLine::Normal(composite) => composite.char_length(),
+            OwnedLine::TableRow(row) => row.cells.iter().map(OwnedComposite::char_length).sum(),
+            _ => 0,
+        }
+    }
+    pub fn is_table_row(&self) -> bool {
+        matches!(self, OwnedLine::TableRow(_))
+    }
+    pub fn is_table_part(&self) -> bool {
+        !matches!(self, OwnedLine::Normal(_))
+    }
+    pub fn is_code(&self) -> bool {
+        match self {
+            OwnedLine::Normal(composite) => composite.is_code(),
+            _ => false,
+        }
+    }
+    pub fn code_fence_lang(&self) -> Option<&str> {
+        match self {
+            OwnedLine::CodeFence(composite) => {
+                composite.compounds.first().map(OwnedCompound::as_str)
+            }
+            _ => None,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        self.as_line().span()
+    }
+}
+
+impl Text<'_> {
+    pub fn into_owned(self) -> OwnedText {
+        OwnedText {
+            lines: self.lines.into_iter().map(Line::into_owned).collect(),
+            code_langs: self
+                .code_langs
+                .into_iter()
+                .map(|(idx, lang)| (idx, lang.to_string()))
+                .collect(),
+        }
+    }
+    /// build an owned copy of the text
+    pub fn to_owned_text(&self) -> OwnedText {
+        self.clone().into_owned()
+    }
+}
+
+impl OwnedText {
+    pub fn as_text(&self) -> Text<'_> {
+        Text {
+            lines: self.lines.iter().map(OwnedLine::as_line).collect(),
+            code_langs: self
+                .code_langs
+                .iter()
+                .map(|(idx, lang)| (*idx, lang.as_str()))
+                .collect(),
+        }
+    }
+    /// parse a markdown text into an owned text
+    pub fn parse(
+        md: &str,
+        options: Options,
+    ) -> Self {
+        parse_text(md, options).into_owned()
+    }
+    pub fn unresolved_references(&self) -> Vec<&str> {
+        self.as_text().unresolved_references()
+    }
+}
+
+impl From<Text<'_>> for OwnedText {
+    fn from(text: Text<'_>) -> Self {
+        text.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn owned_text() -> OwnedText {
+        let md = String::from("# Title\n* an [item](https://dystroy.org)\n|a|*b*|\n|-:|-\n---");
+        OwnedText::parse(&md, Options::default())
+        // md is dropped here
+    }
+
+    #[test]
+    fn owned_text_outlives_source() {
+        let text = owned_text();
+        assert_eq!(text.lines.len(), 5);
+        assert_eq!(
+            text.as_text(),
+            parse_text(
+                "# Title\n* an [item](https://dystroy.org)\n|a|*b*|\n|-:|-\n---",
+                Options::default()
+            )
+        );
+        assert_eq!(
+            text.lines[1],
+            OwnedLine::Normal(OwnedComposite {
+                style: CompositeStyle::ListItem(0),
+                compounds: vec![
+                    Compound::raw_str("an ").into_owned(),
+                    Compound::raw_str("item")
+                        .link(Link::new("https://dystroy.org"))
+                        .into_owned(),
+                ],
+                quote_depth: 0,
+            })
+        );
+        assert_eq!(text.lines[1].char_length(), 7);
+        assert_eq!(text.lines[1].span(), Some(Span::new(1, 10, 18)));
+        assert!(text.lines[2].is_table_row());
+    }
+
+    #[test]
+    fn owned_text_can_be_sent() {
+        let text = owned_text();
+        let handle = std::thread::spawn(move || text.lines.len());
+        assert_eq!(handle.join().unwrap(), 5);
+    }
+}
@@ -0,0 +1,980 @@
+//! Writing markdown from texts, lines and composites, so that
+//! parsing the produced markdown gives back the same structure.
+//!
+//! Some structures, which can't be produced by parsing, can't
+//! be written back exactly, for example inline code containing
+//! a backtick or a list item deeper than 3.
+//!
+//! Some others can't be written back exactly because the parser
+//! doesn't allow escaping what they need: a link just after a `!`
+//! is read as an image.
+//!
+//! Chars are only escaped where they'd be read as markup, which is
+//! checked by parsing the written line. As an escape starts a new
+//! compound, a compound whose chars must be escaped comes back split.
+
+use {
+    crate::*,
+    std::fmt::{
+        self,
+        Write,
+    },
+};
+
+impl Text<'_> {
+    /// build the markdown of the text
+    ///
+    /// The definitions of the resolved reference links are written
+    /// at the end of the text, after an empty line when the text ends
+    /// with a paragraph, which would take them as its continuation.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let mut between_fences = false;
+        let mut after_setext_content = false;
+        let mut after_paragraph_text = false;
+        let mut item_columns: Vec<(u8, usize)> = Vec::new(); // level and content column
+        let mut unkept_fence: Option<u8> = None; // quote depth of the block fenced back
+        for (idx, line) in self.lines.iter().enumerate() {
+            let lang = self.code_lang(idx);
+            if let Some(quote_depth) = unkept_fence {
+                let in_block = lang.is_none() && matches!(line, Line::Normal(c) if c.is_code());
+                if !in_block {
+                    write_fence(&mut md, quote_depth, None);
+                    between_fences = false;
+                    unkept_fence = None;
+                }
+            }
+            if let Some(lang) = lang {
+                // the fences of the block weren't kept but its language was
+                let quote_depth = line.quote_depth();
+                write_fence(&mut md, quote_depth, Some(lang));
+                between_fences = true;
+                unkept_fence = Some(quote_depth);
+            }
+            match line {
+                Line::CodeFence(_) => {
+                    between_fences = !between_fences;
+                    write_checked_line(&mut md, line);
+                }
+                Line::Normal(composite) if between_fences && composite.is_code() => {
+                    // lines between fences aren't indented
+                    let empty = composite.char_length() == 0;
+                    write_quote_prefix(&mut md, composite.quote_depth, empty);
+                    for compound in &composite.compounds {
+                        md.push_str(compound.src);
+                    }
+                }
+                Line::HorizontalRule if after_setext_content => {
+                    // `---` would make a header of the previous line
+                    md.push_str("***");
+                }
+                Line::Normal(Composite {
+                    style: CompositeStyle::ListItemContinuation(level),
+                    compounds,
+                    quote_depth: 0,
+                }) => {
+                    // the continuation starts at the content column of its item
+                    let column = item_columns
+                        .iter()
+                        .find(|(l, _)| l == level)
+                        .map_or(usize::from(*level) + 2, |&(_, column)| column);
+                    for _ in 0..column {
+                        md.push(' ');
+                    }
+                    // the continuation is checked as the paragraph it would be alone
+                    let paragraph = Line::new_paragraph(compounds.clone());
+                    write_lightly(&mut md, &paragraph, |md, escaping| {
+                        write_line_start(md, compounds, escaping);
+                    });
+                }
+                _ => {
+                    let start = md.len();
+                    write_checked_line(&mut md, line);
+                    if after_setext_content && setext_level(&md[start..]).is_some() {
+                        md.insert(start, '\\');
+                    }
+                    if cfg!(feature = "escaping")
+                        && !after_paragraph_text
+                        && parser::link_definition(&md[start..]).is_some()
+                    {
+                        // the line would be read as a link definition
+                        md.insert(start, '\\');
+                    }
+                    if let Line::Normal(Composite {
+                        style:
+                            CompositeStyle::ListItem(level)
+                            | CompositeStyle::TaskListItem { level, .. }
+                            | CompositeStyle::OrderedListItem { level, .. },
+                        quote_depth: 0,
+                        ..
+                    }) = *line
+                    {
+                        if let Some(column) = parser::list_item_content_column(&md[start..]) {
+                            item_columns.retain(|&(l, _)| l < level);
+                            item_columns.push((level, column));
+                        }
+                    }
+                }
+            }
+            after_setext_content = parser::is_setext_content(line);
+            after_paragraph_text = !between_fences && parser::is_paragraph_text(line);
+            md.push('\n');
+        }
+        if let Some(quote_depth) = unkept_fence {
+            write_fence(&mut md, quote_depth, None);
+        }
+        let mut definitions: Vec<(&str, Link<'_>)> = Vec::new();
+        for line in &self.lines {
+            let links = line
+                .composites()
+                .iter()
+                .flat_map(|c| &c.compounds)
+                .filter_map(|compound| compound.link);
+            for link in links {
+                let label = match link.reference {
+                    Some(label) => label,
+                    None => continue,
+                };
+                if link.is_unresolved()
+                    || definitions
+                        .iter()
+                        .any(|(defined, _)| parser::link_labels_match(defined, label))
+                {
+                    continue;
+                }
+                definitions.push((label, link));
+            }
+        }
+        if after_paragraph_text && !definitions.is_empty() {
+            md.push('\n');
+        }
+        for (label, link) in definitions {
+            let _ = write!(md, "[{label}]:");
+            write_destination(&mut md, &link);
+            md.push('\n');
+        }
+        md
+    }
+}
+
+impl Line<'_> {
+    /// build the markdown of the line
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        write_checked_line(&mut md, self);
+        md
+    }
+}
+
+impl Composite<'_> {
+    /// build the markdown of the composite, as a line
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let line = Line::Normal(self.clone());
+        write_lightly(&mut md, &line, |md, escaping| {
+            write_composite(md, self, escaping);
+        });
+        md
+    }
+}
+
+impl Compound<'_> {
+    /// build the markdown of the compound, with its style markers
+    /// and, when it's part of a link, the target of the link
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let compounds = std::slice::from_ref(self);
+        let line = Line::new_paragraph(compounds.to_vec());
+        write_lightly(&mut md, &line, |md, escaping| {
+            write_compounds(md, compounds, escaping);
+        });
+        md
+    }
+}
+
+impl fmt::Display for Text<'_> {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.to_markdown())
+    }
+}
+
+impl fmt::Display for Line<'_> {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.to_markdown())
+    }
+}
+
+impl fmt::Display for Composite<'_> {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.to_markdown())
+    }
+}
+
+/// write a code fence line, with its newline
+fn write_fence(
+    md: &mut String,
+    quote_depth: u8,
+    lang: Option<&str>,
+) {
+    let fence = Line::CodeFence(Composite {
+        style: CompositeStyle::Paragraph,
+        compounds: lang.map(Compound::raw_str).into_iter().collect(),
+        quote_depth,
+    });
+    write_checked_line(md, &fence);
+    md.push('\n');
+}
+
+fn write_checked_line(
+    md: &mut String,
+    line: &Line<'_>,
+) {
+    write_lightly(md, line, |md, escaping| write_line(md, line, escaping));
+}
+
+fn write_line(
+    md: &mut String,
+    line: &Line<'_>,
+    escaping: Escaping,
+) {
+    match line {
+        Line::Normal(composite) => write_composite(md, composite, escaping),
+        Line::TableRow(row) => {
+            md.push('|');
+            for cell in &row.cells {
+                write_cell(md, cell, escaping);
+                md.push('|');
+            }
+        }
+        Line::TableRule(rule) => {
+            for align in &rule.cells {
+                md.push_str(align.col_spec());
+            }
+            md.push('|');
+        }
+        Line::HorizontalRule => md.push_str("---"),
+        Line::CodeFence(composite) => {
+            write_quote_prefix(md, composite.quote_depth, false);
+            md.push_str("```");
+            write_compounds(md, &composite.compounds, escaping);
+        }
+    }
+}
+
+fn write_composite(
+    md: &mut String,
+    composite: &Composite<'_>,
+    escaping: Escaping,
+) {
+    if composite.is_quoted() {
+        // an empty quote is just made of the markers
+        let empty = composite.is_empty()
+            && matches!(
+                composite.style,
+                CompositeStyle::Paragraph | CompositeStyle::Quote
+            );
+        write_quote_prefix(md, composite.quote_depth.max(1), empty);
+    }
+    match composite.style {
+        CompositeStyle::Paragraph => {
+            write_line_start(md, &composite.compounds, escaping);
+        }
+        CompositeStyle::Header(level) => {
+            for _ in 0..level {
+                md.push('#');
+            }
+            md.push(' ');
+            let start = md.len();
+            write_compounds(md, &composite.compounds, escaping);
+            let content = &md[start..];
+            if strip_closing_hashes(content).len() != content.len() {
+                // a closing sequence keeps the ending '#' in the content
+                md.push_str(" #");
+            }
+        }
+        CompositeStyle::Code => {
+            md.push_str("    ");
+            for compound in &composite.compounds {
+                md.push_str(compound.src);
+            }
+        }
+        _ => write_item(md, composite, escaping),
+    }
+}
+
+/// write a composite in a table cell, where there's no header
+/// nor code
+fn write_cell(
+    md: &mut String,
+    composite: &Composite<'_>,
+    escaping: Escaping,
+) {
+    if composite.is_quoted() {
+        write_quote_prefix(md, composite.quote_depth.max(1), false);
+    }
+    write_item(md, composite, escaping);
+}
+
+/// write a composite which may be a list item, after the quote
+/// markers, if any
+fn write_item(
+    md: &mut String,
+    composite: &Composite<'_>,
+    escaping: Escaping,
+) {
+    match composite.style {
+        CompositeStyle::ListItem(level) => {
+            push_indent(md, level);
+            md.push_str("* ");
+        }
+        CompositeStyle::TaskListItem { level, checked } => {
+            push_indent(md, level);
+            md.push_str(if checked { "* [x] " } else { "* [ ] " });
+        }
+        CompositeStyle::OrderedListItem { level, index } => {
+            push_indent(md, level);
+            let _ = write!(md, "{index}. ");
+        }
+        CompositeStyle::ListItemContinuation(level) => {
+            // the indentation makes it follow the item even after
+            // a blank line
+            push_indent(md, level.saturating_add(2));
+            write_line_start(md, &composite.compounds, escaping);
+            return;
+        }
+        _ => {
+            write_line_start(md, &composite.compounds, escaping);
+            return;
+        }
+    }
+    write_compounds(md, &composite.compounds, escaping);
+}
+
+/// write the `>` markers of a quote, and the space separating them
+/// from the content
+fn write_quote_prefix(
+    md: &mut String,
+    quote_depth: u8,
+    empty: bool,
+) {
+    for _ in 0..quote_depth {
+        md.push('>');
+    }
+    if quote_depth > 0 && !empty {
+        md.push(' ');
+    }
+}
+
+fn push_indent(
+    md: &mut String,
+    level: u8,
+) {
+    for _ in 0..level {
+        md.push(' ');
+    }
+}
+
+/// write the compounds of a paragraph, escaping the start of the
+/// line when it would be read as a header, a quote, a list item
+/// or a rule
+fn write_line_start(
+    md: &mut String,
+    compounds: &[Compound<'_>],
+    escaping: Escaping,
+) {
+    let start = md.len();
+    write_compounds(md, compounds, escaping);
+    let written = &md[start..];
+    if cfg!(feature = "escaping") && escape::needs_line_start_escape(written) {
+        md.insert(start, '\\');
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    code: bool,
+    strikeout: bool,
+}
+
+impl Style {
+    fn of(compound: &Compound<'_>) -> Self {
+        Self {
+            bold: compound.bold,
+            italic: compound.italic,
+            code: compound.code,
+            strikeout: compound.strikeout,
+        }
+    }
+}
+
+/// write the markers changing the style from `from` to `to`
+///
+/// Inline code must be closed before any other marker, as they'd
+/// be read as code, and opened after them.
+fn write_style_change(
+    md: &mut String,
+    from: Style,
+    to: Style,
+) {
+    if from.code && !to.code {
+        md.push('`');
+    }
+    if from.italic != to.italic {
+        md.push('*');
+    }
+    if from.bold != to.bold {
+        md.push_str("**");
+    }
+    if from.strikeout != to.strikeout {
+        md.push_str("~~");
+    }
+    if !from.code && to.code {
+        md.push('`');
+    }
+}
+
+/// how much of the text of the compounds is escaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escaping {
+    /// only what would be read differently: the start of a compound
+    /// which would otherwise merge with the previous one, and the
+    /// backslashes before an escapable char or a marker
+    Minimal,
+    /// like `Minimal`, but the styles still open at the end of the
+    /// line aren't closed, as the parser closes them
+    Unclosed,
+    /// all escapable chars
+    Full,
+}
+
+/// write the lightest markdown which parses back into the expected line
+///
+/// `Minimal` then `Unclosed` are tried, and must give back the same
+/// compounds. When they don't, the fully escaped markdown is written,
+/// with only the escapes needed to keep the styling of every char.
+fn write_lightly(
+    md: &mut String,
+    expected: &Line<'_>,
+    write: impl Fn(&mut String, Escaping),
+) {
+    if !cfg!(feature = "escaping") {
+        write(md, Escaping::Full);
+        return;
+    }
+    let autolinks = expected
+        .composites()
+        .iter()
+        .flat_map(|composite| &composite.compounds)
+        .any(is_written_autolink);
+    let options = Options::default().autolinks(autolinks);
+    for escaping in [Escaping::Minimal, Escaping::Unclosed] {
+        let mut candidate = String::new();
+        write(&mut candidate, escaping);
+        if same_compounds(expected, &read_line(&candidate, options)) {
+            md.push_str(&candidate);
+            return;
+        }
+    }
+    let mut written = String::new();
+    write(&mut written, Escaping::Full);
+    let mut escapes = Vec::new();
+    let mut chars = written.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == '\\' {
+            escapes.push(idx);
+            chars.next();
+        }
+    }
+    // the escapes are tried from the end, so that the first char
+    // of a marker is the one left escaped
+    for idx in escapes.into_iter().rev() {
+        let mut candidate = written.clone();
+        candidate.remove(idx);
+        if same_reading(&written, &candidate, options) {
+            written = candidate;
+        }
+    }
+    md.push_str(&written);
+}
+
+/// tell whether both markdown lines are read the same, with or
+/// without the underscore emphasis, as the text doesn't tell how
+/// it was parsed
+fn same_reading(
+    a: &str,
+    b: &str,
+    options: Options,
+) -> bool {
+    [false, true].iter().all(|&underscore_emphasis| {
+        let options = options.underscore_emphasis(underscore_emphasis);
+        same_styled_chars(&read_line(a, options), &read_line(b, options))
+    })
+}
+
+/// parse a line, with its autolinks if the options say so
+fn read_line(
+    md: &str,
+    options: Options,
+) -> Line<'_> {
+    let mut line = parser::LineParser::from(md).options(options).line();
+    if options.autolinks {
+        parser::detect_autolinks(std::slice::from_mut(&mut line));
+    }
+    line
+}
+
+/// tell whether the lines are of the same kind, with composites
+/// of the same style made of the same compounds
+///
+/// Only the presence of links is compared, as reference links
+/// aren't resolved when parsing a single line.
+fn same_compounds(
+    a: &Line<'_>,
+    b: &Line<'_>,
+) -> bool {
+    same_structure(a, b)
+        && a.composites().iter().zip(b.composites()).all(|(a, b)| {
+            a.compounds.len() == b.compounds.len()
+                && a.compounds.iter().zip(&b.compounds).all(|(a, b)| {
+                    a.src == b.src
+                        && Style::of(a) == Style::of(b)
+                        && a.link.map(|l| (l.image, l.autolink))
+                            == b.link.map(|l| (l.image, l.autolink))
+                })
+        })
+}
+
+/// tell whether the lines are of the same kind, with composites
+/// of the same style made of the same chars, styled the same way
+fn same_styled_chars(
+    a: &Line<'_>,
+    b: &Line<'_>,
+) -> bool {
+    fn styled_chars<'c>(
+        composite: &'c Composite<'_>
+    ) -> impl Iterator<Item = (char, &'c Compound<'c>)> {
+        composite
+            .compounds
+            .iter()
+            .flat_map(|compound| compound.src.chars().map(move |c| (c, compound)))
+    }
+    same_structure(a, b)
+        && a.composites().iter().zip(b.composites()).all(|(a, b)| {
+            styled_chars(a).count() == styled_chars(b).count()
+                && styled_chars(a)
+                    .zip(styled_chars(b))
+                    .all(|((ca, a), (cb, b))| {
+                        ca == cb && Style::of(a) == Style::of(b) && a.link == b.link
+                    })
+        })
+}
+
+fn same_structure(
+    a: &Line<'_>,
+    b: &Line<'_>,
+) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+        && a.composites().len() == b.composites().len()
+        && a.composites()
+            .iter()
+            .zip(b.composites())
+            .all(|(a, b)| a.style == b.style && a.quote_depth == b.quote_depth)
+}
+
+fn write_compounds(
+    md: &mut String,
+    compounds: &[Compound<'_>],
+    escaping: Escaping,
+) {
+    let mut style = Style::default();
+    let mut link: Option<Link<'_>> = None;
+    for (idx, compound) in compounds.iter().enumerate() {
+        // autolinks are written as urls, to be found again by the parser
+        let autolink = is_written_autolink(compound);
+        let compound_link = if autolink { None } else { compound.link };
+        if compound_link != link {
+            // styles are closed at the boundaries of links
+            write_style_change(md, style, Style::default());
+            style = Style::default();
+            if let Some(link) = &link {
+                md.push(']');
+                write_link_target(md, link);
+            }
+            if let Some(new_link) = &compound_link {
+                if new_link.image {
+                    md.push('!');
+                }
+                md.push('[');
+            }
+            link = compound_link;
+        }
+        let new_style = Style::of(compound);
+        // without a marker between two compounds, only an escape
+        // makes the parser start a new compound
+        let adjacent = idx > 0
+            && new_style == style
+            && compounds[idx - 1].link == link
+            && !is_written_autolink(&compounds[idx - 1]);
+        write_style_change(md, style, new_style);
+        style = new_style;
+        if autolink && !compound.src.starts_with("www.") {
+            let _ = write!(md, "<{}>", compound.src);
+            continue;
+        }
+        if autolink || compound.code || !cfg!(feature = "escaping") {
+            md.push_str(compound.src);
+            continue;
+        }
+        // whether something is written after the compound
+        let followed = idx + 1 < compounds.len()
+            || link.is_some()
+            || (escaping != Escaping::Unclosed && style != Style::default());
+        let mut chars = compound.src.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let escaped = match escaping {
+                Escaping::Full => escape::is_escapable(c),
+                _ if i == 0 && adjacent => escape::is_escapable(c),
+                _ if c == '\\' => match chars.peek() {
+                    Some(&(_, next)) => escape::is_escapable(next),
+                    None => followed,
+                },
+                _ => false,
+            };
+            if escaped {
+                md.push('\\');
+            }
+            md.push(c);
+            if escaping == Escaping::Unclosed
+                && c == '*'
+                && chars.peek().is_none()
+                && !followed
+                && style.italic
+            {
+                // an escaped star after a star ending an italic
+                // part closes it, keeping the first star as text
+                md.push_str("\\*");
+            }
+        }
+    }
+    if escaping != Escaping::Unclosed || link.is_some() {
+        write_style_change(md, style, Style::default());
+    }
+    if let Some(link) = &link {
+        md.push(']');
+        write_link_target(md, link);
+    }
+}
+
+/// tell whether the compound is an autolink which can be written
+/// as its url, either bare (for `www.` urls) or between `<` and `>`
+fn is_written_autolink(compound: &Compound<'_>) -> bool {
+    compound
+        .link
+        .is_some_and(|link| link.autolink && !link.image && link.url == compound.src)
+}
+
+/// write what follows the text of a link: the label of a reference
+/// link, or the destination of an inline link
+fn write_link_target(
+    md: &mut String,
+    link: &Link<'_>,
+) {
+    if let Some(label) = link.reference {
+        let _ = write!(md, "[{label}]");
+    } else {
+        md.push('(');
+        write_destination(md, link);
+        md.push(')');
+    }
+}
+
+/// write the url and the title of a link
+fn write_destination(
+    md: &mut String,
+    link: &Link<'_>,
+) {
+    let url = link.url;
+    let mut depth = 0;
+    let balanced = url.chars().all(|c| {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        true
+    }) && depth == 0;
+    if balanced && !url.starts_with('<') && !url.contains([' ', '|']) {
+        md.push_str(url);
+    } else {
+        let _ = write!(md, "<{url}>");
+    }
+    if let Some(title) = link.title {
+        let (open, close) = if !title.contains('"') {
+            ('"', '"')
+        } else if !title.contains('\'') {
+            ('\'', '\'')
+        } else {
+            ('(', ')')
+        };
+        let _ = write!(md, " {open}{title}{close}");
+    }
+}
+
+#[cfg(all(test, feature = "escaping"))]
+mod tests {
+    use crate::*;
+
+    fn check_round_trip(
+        md: &str,
+        options: Options,
+    ) {
+        let text = parse_text(md, options);
+        let written = text.to_markdown();
+        assert_eq!(parse_text(&written, options), text, "written:\n{written}");
+    }
+
+    #[test]
+    fn write_styles() {
+        assert_eq!(
+            parse_line("Hello ~~wolrd~~ **World**. *Code*: `sqrt(π/2)`").to_string(),
+            "Hello ~~wolrd~~ **World**. *Code*: `sqrt(π/2)`",
+        );
+        assert_eq!(
+            parse_inline("*Italic then **bold and italic `and some *code*`** and italic*")
+                .to_string(),
+            "*Italic then **bold and italic `and some *code*`** and italic*",
+        );
+        assert_eq!(
+            Composite::from(vec![
+                Compound::raw_str("2*π*r "),
+                Compound::raw_str("[a|b]").bold(),
+            ])
+            .to_string(),
+            r"2\*π\*r **[a|b]**",
+        );
+    }
+
+    #[test]
+    fn write_lines() {
+        assert_eq!(parse_line("### a *header*").to_string(), "### a *header*");
+        assert_eq!(parse_line("  - item").to_string(), "  * item");
+        assert_eq!(parse_line("3) third").to_string(), "3. third");
+        assert_eq!(parse_line("- [X] done").to_string(), "* [x] done");
+        assert_eq!(parse_line("> > a").to_string(), ">> a");
+        assert_eq!(parse_line("> - *item*").to_string(), "> * *item*");
+        assert_eq!(parse_line(">> ## title").to_string(), ">> ## title");
+        assert_eq!(parse_line(">>").to_string(), ">>");
+        assert_eq!(parse_line(">     code").to_string(), ">     code");
+        assert_eq!(parse_line("|>> a|> 1. b|").to_string(), "|>> a|> 1. b|");
+        assert_eq!(parse_line(" + [ ] to do").to_string(), " * [ ] to do");
+        assert_eq!(parse_line("> quote").to_string(), "> quote");
+        assert_eq!(parse_line("|:-:|-|").to_string(), "|:-:|-|");
+        assert_eq!(parse_line("|a| *b* |").to_string(), "|a|*b*|");
+        assert_eq!(parse_line("-----").to_string(), "---");
+        assert_eq!(
+            Line::new_code(Compound::raw_str("a*b")).to_string(),
+            "    a*b"
+        );
+        // paragraphs which would be read as something else
+        assert_eq!(
+            Line::raw_str("# not a header").to_string(),
+            r"\# not a header"
+        );
+        assert_eq!(
+            Line::raw_str("1. not a list").to_string(),
+            r"\1. not a list"
+        );
+        assert_eq!(Line::raw_str("---").to_string(), r"\---");
+        assert_eq!(Line::raw_str("1984").to_string(), "1984");
+        assert_eq!(
+            Line::new_header(2, vec![Compound::raw_str("issue #")]).to_string(),
+            "## issue # #"
+        );
+        // lines which would be read as setext underlines
+        let text = Text {
+            lines: vec![
+                Line::raw_str("a"),
+                Line::HorizontalRule,
+                Line::raw_str("b"),
+                Line::raw_str("=="),
+                Line::raw_str("c"),
+                Line::raw_str("-"),
+            ],
+            code_langs: Vec::new(),
+        };
+        assert_eq!(text.to_string(), "a\n***\nb\n\\==\nc\n\\-\n");
+    }
+
+    #[test]
+    fn write_links() {
+        assert_eq!(
+            parse_line(r#"see [the *doc*](https://dystroy.org "Doc")!"#).to_string(),
+            r#"see [the *doc*](https://dystroy.org "Doc")!"#,
+        );
+        assert_eq!(
+            parse_line("![an image](<img (1).png>)").to_string(),
+            "![an image](<img (1).png>)",
+        );
+        // autolinks are written as urls, as their href depends on it
+        let md = "see *www.dystroy.org*, <https://dystroy.org/a_b> or me@dystroy.org";
+        let options = Options::default().autolinks(true);
+        let text = parse_text(md, options);
+        assert_eq!(
+            text.to_string(),
+            "see *www.dystroy.org*, <https://dystroy.org/a_b> or <me@dystroy.org>\n",
+        );
+        check_round_trip(md, options);
+    }
+
+    #[test]
+    fn round_trips() {
+        let md = r#"# Title with **bold** and [a link][ref]
+Setext title
+---
+paragraph
+***
+## issue # ##
+
+Some *italic*, ~~striked~~ and `code`, **bold *and italic***,
+escaped \*stars\*, a \\ and an image: ![alt](img.png "The title").
+\# not a header
+\- not a list item
+
+* item with *italic*
+  * deeper
+1. first
+2) second
+> quoted [link](<a b>)
+>
+|:-:|-:|
+|**a**|`b|c`|
+|- c|[d](e)||
+---
+    some code
+```rust
+let a = *b;
+```
+[ref]: https://dystroy.org 'Dystroy'
+"#;
+        check_round_trip(md, Options::default());
+        check_round_trip(md, Options::default().keep_code_fences(true));
+        check_round_trip(md, Options::default().autolinks(true));
+    }
+
+    /// chars which aren't read as markup are written unescaped, so
+    /// that the compounds are the same after a round trip
+    #[cfg(feature = "escaping")]
+    #[test]
+    fn round_trip_compounds() {
+        for md in [
+            "a_b",
+            "snake_case",
+            "~a~",
+            "a ~ b",
+            "[a]",
+            "[a](b",
+            "a\\b",
+            "x | y",
+            "_a_",
+            "**a*",
+            "*a*\\*",
+            "\\!not image",
+            "a\\*b\\\\",
+        ] {
+            check_round_trip(md, Options::default());
+        }
+        assert_eq!(
+            parse_text("a_b [a] x | y", Options::default()).to_string(),
+            "a_b [a] x | y\n"
+        );
+        assert_eq!(Compound::raw_str("a*b").bold().to_markdown(), r"**a\*b**",);
+    }
+
+    #[test]
+    fn round_trip_underscores() {
+        let md = "_italic_ and __bold__ with snake_case and \\_escaped\\_ underscores\n";
+        for options in [
+            Options::default(),
+            Options::default().underscore_emphasis(true),
+        ] {
+            check_round_trip(md, options);
+        }
+        let text = parse_text(md, Options::default().underscore_emphasis(true));
+        assert_eq!(
+            text.to_markdown(),
+            "*italic* and **bold** with snake_case and \\_escaped\\_ underscores\n",
+        );
+    }
+
+    #[test]
+    fn write_toggled_tasks() {
+        let mut text = parse_text("* [ ] a\n  * [x] b\n* c", Options::default());
+        let states: Vec<Option<bool>> = text
+            .lines
+            .iter_mut()
+            .map(|line| match line {
+                Line::Normal(composite) => composite.toggle_checked(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(states, vec![Some(true), Some(false), None]);
+        assert_eq!(text.to_string(), "* [x] a\n  * [ ] b\n* c\n");
+        // a list item whose content looks like a task marker stays one
+        #[cfg(feature = "escaping")]
+        {
+            let line = Line::new_list_item(0, vec![Compound::raw_str("[ ] a")]);
+            assert_eq!(line.to_string(), r"* \[ ] a");
+            match parse_line(&line.to_string()) {
+                Line::Normal(composite) => {
+                    assert_eq!(composite.style, CompositeStyle::ListItem(0));
+                    let content: String = composite.compounds.iter().map(|c| c.as_str()).collect();
+                    assert_eq!(content, "[ ] a");
+                }
+                line => panic!("unexpected line: {:?}", line),
+            }
+        }
+    }
+
+    #[test]
+    fn write_list_item_continuations() {
+        let md = "* a\n  lazy\n  * b\n\n    more **b**\n    \\- not an item\n12. c\n  d";
+        let text = parse_text(md, Options::default());
+        let written = text.to_string();
+        assert_eq!(parse_text(&written, Options::default()), text);
+        #[cfg(feature = "escaping")]
+        assert_eq!(
+            written,
+            "* a\n  lazy\n  * b\n\n    more **b**\n    \\- not an item\n12. c\n    d\n",
+        );
+    }
+
+    #[test]
+    fn write_nested_quotes() {
+        let md = "> a\n>> b\n>> * c\n>\n> ```\n> code\n>\n> ```\n> # d\n";
+        let text = parse_text(md, Options::default().keep_code_fences(true));
+        assert_eq!(text.to_string(), md);
+        // a quoted paragraph which would be read as something else
+        #[cfg(feature = "escaping")]
+        {
+            let line = Line::Normal(Composite {
+                style: CompositeStyle::Quote,
+                compounds: vec![Compound::raw_str("> - x")],
+                quote_depth: 1,
+            });
+            assert_eq!(line.to_string(), r"> \> - x");
+            assert_eq!(parse_line(&line.to_string()).quote_depth(), 1);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// the position of a parsed element in the markdown source
+///
+/// `start` and `end` are byte indexes in the string which was
+/// parsed: the whole text for `parse_text` and `Text::from_str`,
+/// the line for `Line::from` or `Text::from_md_lines`, the
+/// snippet for `parse_inline`.
+///
+/// `line` is the index of the line in the parsed text, 0 when
+/// parsing a single line.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(
+        line: usize,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        Self { line, start, end }
+    }
+    pub fn range(self) -> Range<usize> {
+        self.start..self.end
+    }
+    pub fn len(self) -> usize {
+        self.end - self.start
+    }
+    pub fn is_empty(self) -> bool {
+        self.end == self.start
+    }
+    /// return the part of the span from `r_start` to `r_end`,
+    /// which are relative to the start of this span
+    pub fn sub(
+        self,
+        r_start: usize,
+        r_end: usize,
+    ) -> Self {
+        Self {
+            line: self.line,
+            start: self.start + r_start,
+            end: self.start + r_end,
+        }
+    }
+    /// return the smallest span containing both spans, on the
+    /// line of the first one
+    pub fn union(
+        self,
+        other: Self,
+    ) -> Self {
+        Self {
+            line: self.line,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::*;
+use {
+    crate::*,
+    std::ops::Range,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TableRow<'a> {
@@ -10,6 +13,23 @@ pub struct TableRule {
     pub cells: Vec<Alignment>,
 }
 
+/// a table of a text, grouping consecutive table rows and rules
+///
+/// All rows, the header and the alignments have the same number
+/// of cells, the shorter ones being completed with empty cells
+/// and unspecified alignments.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Table<'a> {
+    /// the first row, when it's followed by a rule
+    pub header: Option<TableRow<'a>>,
+    /// the alignments of the first rule
+    pub alignments: Vec<Alignment>,
+    /// the rows, not including the header
+    pub rows: Vec<TableRow<'a>>,
+    /// the range of the table lines in the text
+    pub line_range: Range<usize>,
+}
+
 impl TableRow<'_> {
     /// Try to read the cells as formatting cells
     ///
@@ -49,3 +69,88 @@ impl TableRow<'_> {
         Some(formats)
     }
 }
+
+impl<'a> Table<'a> {
+    /// build a table from consecutive table rows and rules
+    pub(crate) fn from_lines(
+        lines: &[Line<'a>],
+        line_range: Range<usize>,
+    ) -> Self {
+        let mut header = None;
+        let mut alignments = None;
+        let mut rows = Vec::new();
+        for (idx, line) in lines.iter().enumerate() {
+            match line {
+                Line::TableRow(row) => {
+                    let followed_by_rule = matches!(lines.get(idx + 1), Some(Line::TableRule(_)));
+                    if header.is_none() && rows.is_empty() && followed_by_rule {
+                        header = Some(row.clone());
+                    } else {
+                        rows.push(row.clone());
+                    }
+                }
+                Line::TableRule(rule) if alignments.is_none() => {
+                    alignments = Some(rule.cells.clone());
+                }
+                _ => {}
+            }
+        }
+        let mut alignments = alignments.unwrap_or_default();
+        let column_count = header
+            .iter()
+            .chain(&rows)
+            .map(|row| row.cells.len())
+            .chain(Some(alignments.len()))
+            .max()
+            .unwrap_or(0);
+        alignments.resize(column_count, Alignment::Unspecified);
+        for row in header.iter_mut().chain(&mut rows) {
+            row.cells.resize_with(column_count, Composite::default);
+        }
+        Self {
+            header,
+            alignments,
+            rows,
+            line_range,
+        }
+    }
+    pub fn column_count(&self) -> usize {
+        self.alignments.len()
+    }
+}
+
+#[test]
+fn text_tables() {
+    let md = r#"Some tables:
+|:-:|:-
+|**a**|**b**|
+|-:|-
+|c|d|e|
+|f|
+and
+|g|h|"#;
+    let text = parse_text(md, Options::default());
+    let tables = text.tables();
+    assert_eq!(tables.len(), 2);
+    let table = &tables[0];
+    assert_eq!(table.line_range, 1..6);
+    assert_eq!(
+        table.alignments,
+        vec![Alignment::Center, Alignment::Left, Alignment::Unspecified],
+    );
+    assert_eq!(
+        table.header,
+        Some(TableRow {
+            cells: vec![
+                Composite::from(vec![Compound::raw_str("a").bold()]),
+                Composite::from(vec![Compound::raw_str("b").bold()]),
+                Composite::default(),
+            ]
+        }),
+    );
+    assert_eq!(table.rows.len(), 2);
+    assert_eq!(table.rows[1].cells.len(), 3);
+    assert_eq!(tables[1].header, None);
+    assert_eq!(tables[1].column_count(), 2);
+    assert_eq!(tables[1].line_range, 7..8);
+}
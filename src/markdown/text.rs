@@ -4,6 +4,9 @@ use crate::*;
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct Text<'a> {
     pub lines: Vec<Line<'a>>,
+    /// the languages of the fenced code blocks whose fences weren't
+    /// kept, with the index of the first line of the block
+    pub code_langs: Vec<(usize, &'a str)>,
 }
 
 impl<'s> From<&'s str> for Text<'s> {
@@ -21,19 +24,78 @@ impl<'s> Text<'s> {
         s: &'s str,
         options: Options,
     ) -> Self {
-        crate::parser::parse_lines(s.lines(), options)
+        crate::parser::parse_lines(s.lines(), Some(s), options)
     }
     /// Parse a text from markdown lines.
     pub fn from_md_lines<I>(md_lines: I) -> Self
     where
         I: Iterator<Item = &'s str>,
     {
-        crate::parser::parse_lines(md_lines, Options::default())
+        crate::parser::parse_lines(md_lines, None, Options::default())
     }
     pub fn raw_str(s: &'s str) -> Self {
         let lines = s.lines().map(Line::raw_str).collect();
-        Self { lines }
+        Self {
+            lines,
+            code_langs: Vec::new(),
+        }
     }
+    /// return the language of the fenced code block starting at the
+    /// line, when the fences weren't kept (see `Options::keep_code_fences`)
+    ///
+    /// When they're kept, the language is given by `Line::code_fence_lang`.
+    pub fn code_lang(
+        &self,
+        line_idx: usize,
+    ) -> Option<&'s str> {
+        self.code_langs
+            .iter()
+            .find(|(idx, _)| *idx == line_idx)
+            .map(|(_, lang)| *lang)
+    }
+    /// return the labels of the reference links (`[text][label]`) which
+    /// couldn't be resolved because there was no `[label]: url` definition
+    /// in the parsed markdown. Each label is returned only once.
+    pub fn unresolved_references(&self) -> Vec<&'s str> {
+        let mut labels = Vec::new();
+        for line in &self.lines {
+            for compound in line.composites().iter().flat_map(|c| &c.compounds) {
+                let label = match compound.link {
+                    Some(link) if link.is_unresolved() => link.reference,
+                    _ => None,
+                };
+                if let Some(label) = label {
+                    if !labels.contains(&label) {
+                        labels.push(label);
+                    }
+                }
+            }
+        }
+        labels
+    }
+    /// return the tables of the text, each one being made of
+    /// consecutive table rows and rules
+    pub fn tables(&self) -> Vec<Table<'s>> {
+        let mut tables = Vec::new();
+        let mut start = 0;
+        while start < self.lines.len() {
+            if !is_table_line(&self.lines[start]) {
+                start += 1;
+                continue;
+            }
+            let end = self.lines[start..]
+                .iter()
+                .position(|line| !is_table_line(line))
+                .map_or(self.lines.len(), |len| start + len);
+            tables.push(Table::from_lines(&self.lines[start..end], start..end));
+            start = end;
+        }
+        tables
+    }
+}
+
+fn is_table_line(line: &Line<'_>) -> bool {
+    matches!(line, Line::TableRow(_) | Line::TableRule(_))
 }
 
 #[test]
@@ -0,0 +1,210 @@
+use {
+    crate::*,
+    std::ops::Range,
+};
+
+/// Turn the autolinks (`<https://dystroy.org>`, `<someone@example.com>`),
+/// bare urls (`https://dystroy.org`, `www.dystroy.org`) and email addresses
+/// of the text into link compounds.
+///
+/// Inline code, code blocks and existing links aren't affected.
+pub(crate) fn detect_autolinks(lines: &mut [Line<'_>]) {
+    for line in lines {
+        match line {
+            Line::Normal(composite) if !composite.is_code() => split_autolinks(composite),
+            Line::TableRow(row) => row.cells.iter_mut().for_each(split_autolinks),
+            _ => {}
+        }
+    }
+}
+
+fn split_autolinks(composite: &mut Composite<'_>) {
+    let mut compounds = Vec::new();
+    for compound in composite.compounds.drain(..) {
+        if compound.code || compound.link.is_some() {
+            compounds.push(compound);
+            continue;
+        }
+        let mut start = 0;
+        while let Some((range, url_range)) = find_autolink(&compound.src[start..]) {
+            if range.start > 0 {
+                compounds.push(compound.sub(start, start + range.start));
+            }
+            let url = compound.sub(start + url_range.start, start + url_range.end);
+            let link = Link::new(url.src).autolink();
+            compounds.push(url.link(link));
+            start += range.end;
+        }
+        if start == 0 {
+            compounds.push(compound);
+        } else if start < compound.src.len() {
+            compounds.push(compound.tail(start));
+        }
+    }
+    composite.compounds = compounds;
+}
+
+/// Find the first autolink, bare url or email address in the string.
+///
+/// Return its range and the range of the url, which is smaller for
+/// autolinks as they're wrapped in `<` and `>`.
+fn find_autolink(s: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let bytes = s.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'<' => {
+                if let Some(len) = s[i + 1..].find('>') {
+                    let content = &s[i + 1..i + 1 + len];
+                    if is_uri(content) || email_len(content) == Some(content.len()) {
+                        return Some((i..i + len + 2, i + 1..i + 1 + len));
+                    }
+                }
+            }
+            b'h' | b'w' => {
+                let after_boundary = i == 0
+                    || bytes[i - 1].is_ascii_whitespace()
+                    || matches!(bytes[i - 1], b'(' | b'*' | b'_' | b'~' | b'"' | b'\'');
+                if after_boundary {
+                    if let Some(len) = bare_url_len(&s[i..]) {
+                        return Some((i..i + len, i..i + len));
+                    }
+                }
+            }
+            b'@' => {
+                let start = s[..i]
+                    .bytes()
+                    .rposition(|b| !is_email_local_char(b))
+                    .map_or(0, |p| p + 1);
+                if start < i {
+                    if let Some(len) = email_len(&s[start..]) {
+                        return Some((start..start + len, start..start + len));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Tell whether the string is an absolute URI, like `https://dystroy.org`
+/// or `irc://irc.libera.chat`
+fn is_uri(s: &str) -> bool {
+    let colon = match s.find(':') {
+        Some(colon) => colon,
+        None => return false,
+    };
+    let scheme = &s[..colon];
+    (2..=32).contains(&scheme.len())
+        && scheme.as_bytes()[0].is_ascii_alphabetic()
+        && scheme
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'.' | b'-'))
+        && !s.contains(|c: char| c.is_whitespace() || c == '<')
+}
+
+/// Return the length of the url starting the string, if it's
+/// a `http://`, `https://` or `www.` one
+fn bare_url_len(s: &str) -> Option<usize> {
+    let prefix_len = ["https://", "http://", "www."]
+        .iter()
+        .find(|prefix| s.starts_with(*prefix))?
+        .len();
+    let mut len = s
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(s.len());
+    // trailing punctuation isn't considered part of the url
+    loop {
+        let url = &s[..len];
+        match url.as_bytes()[len - 1] {
+            b'?' | b'!' | b'.' | b',' | b':' | b'*' | b'_' | b'~' | b'\'' | b'"' => {}
+            b')' if url.matches(')').count() > url.matches('(').count() => {}
+            _ => break,
+        }
+        len -= 1;
+    }
+    if len > prefix_len { Some(len) } else { None }
+}
+
+fn is_email_local_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'.' | b'+' | b'-' | b'_')
+}
+
+/// Return the length of the email address starting the string, if any
+fn email_len(s: &str) -> Option<usize> {
+    let at = s.bytes().position(|b| !is_email_local_char(b))?;
+    if at == 0 || s.as_bytes()[at] != b'@' {
+        return None;
+    }
+    let domain = &s[at + 1..];
+    let mut len = domain
+        .bytes()
+        .position(|b| !(b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_')))
+        .unwrap_or(domain.len());
+    while len > 0 && domain.as_bytes()[len - 1] == b'.' {
+        len -= 1;
+    }
+    let domain = &domain[..len];
+    let valid = domain.contains('.')
+        && !domain.ends_with(['-', '_'])
+        && domain.split('.').all(|part| !part.is_empty());
+    if valid { Some(at + 1 + len) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn autolink(s: &str) -> Option<(&str, &str)> {
+        find_autolink(s).map(|(range, url_range)| (&s[range], &s[url_range]))
+    }
+
+    #[test]
+    fn find_autolinks() {
+        assert_eq!(
+            autolink("see <https://dystroy.org>."),
+            Some(("<https://dystroy.org>", "https://dystroy.org"))
+        );
+        assert_eq!(
+            autolink("<irc://irc.libera.chat>"),
+            Some(("<irc://irc.libera.chat>", "irc://irc.libera.chat"))
+        );
+        assert_eq!(
+            autolink("<me@dystroy.org>"),
+            Some(("<me@dystroy.org>", "me@dystroy.org"))
+        );
+        assert_eq!(autolink("a <b> c"), None);
+        assert_eq!(autolink("<not a url>"), None);
+    }
+
+    #[test]
+    fn find_bare_urls() {
+        assert_eq!(
+            autolink("go to https://dystroy.org/broot."),
+            Some(("https://dystroy.org/broot", "https://dystroy.org/broot"))
+        );
+        assert_eq!(
+            autolink("(see www.dystroy.org)"),
+            Some(("www.dystroy.org", "www.dystroy.org"))
+        );
+        assert_eq!(
+            autolink("https://en.wikipedia.org/wiki/Rust_(language), yes"),
+            Some((
+                "https://en.wikipedia.org/wiki/Rust_(language)",
+                "https://en.wikipedia.org/wiki/Rust_(language)"
+            ))
+        );
+        assert_eq!(autolink("nohttps://dystroy.org"), None);
+        assert_eq!(autolink("http:// nothing"), None);
+    }
+
+    #[test]
+    fn find_emails() {
+        assert_eq!(
+            autolink("write to denys.seguret@gmail.com."),
+            Some(("denys.seguret@gmail.com", "denys.seguret@gmail.com"))
+        );
+        assert_eq!(autolink("ping @someone"), None);
+        assert_eq!(autolink("user@localhost"), None);
+    }
+}
@@ -18,6 +18,12 @@ pub struct LineParser<'s> {
     pub(crate) italic: bool,
     pub(crate) bold: bool,
     pub(crate) strikeout: bool,
+    link: Option<Link<'s>>, // the link whose text is being read, if any
+    link_text_end: usize,   // index of the `]` closing the text of the link
+    link_end: usize,        // index just after the `)` ending the link
+    link_has_content: bool,
+    underscore_emphasis: bool,
+    in_template: bool, // whether the `|` of placeholders like `${size|bytes}` don't end cells
 }
 
 impl<'s> LineParser<'s> {
@@ -29,8 +35,46 @@ impl<'s> LineParser<'s> {
             italic: false,
             code: false,
             strikeout: false,
+            link: None,
+            link_text_end: 0,
+            link_end: 0,
+            link_has_content: false,
+            underscore_emphasis: false,
+            in_template: false,
         }
     }
+    /// read the line as a line of a template, where the `|` of placeholders
+    /// with a formatter, like `${size|bytes}`, don't end table cells
+    pub(crate) fn in_template(mut self) -> Self {
+        self.in_template = true;
+        self
+    }
+    /// tell whether the pipe at `idx` ends a table cell, and isn't the
+    /// separator of a formatter in a template placeholder
+    fn is_cell_end(
+        &self,
+        idx: usize,
+    ) -> bool {
+        if !self.in_template {
+            return true;
+        }
+        let name_start = match self.src[..idx].rfind("${") {
+            Some(dollar_idx) => dollar_idx + 2,
+            None => return true,
+        };
+        let closed = self.src[idx + 1..]
+            .find(['|', '}'])
+            .is_some_and(|len| self.src[idx + 1 + len..].starts_with('}'));
+        !(idx > name_start && self.src[name_start..idx].chars().all(is_valid_name_char) && closed)
+    }
+    /// apply the options which are relevant when parsing a line
+    pub fn options(
+        mut self,
+        options: Options,
+    ) -> Self {
+        self.underscore_emphasis = options.underscore_emphasis;
+        self
+    }
     fn close_compound(
         &mut self,
         end: usize,
@@ -38,7 +82,7 @@ impl<'s> LineParser<'s> {
         compounds: &mut Vec<Compound<'s>>,
     ) {
         if end > self.idx {
-            compounds.push(Compound::new(
+            let mut compound = Compound::new(
                 self.src,
                 self.idx,
                 end,
@@ -46,10 +90,103 @@ impl<'s> LineParser<'s> {
                 self.italic,
                 self.code,
                 self.strikeout,
-            ));
+            );
+            if self.link.is_some() {
+                compound.link = self.link;
+                self.link_has_content = true;
+            }
+            compounds.push(compound);
         }
         self.idx = end + tag_length;
     }
+    /// if a link or an image starts at `idx`, close the current compound
+    /// and start reading the text of the link.
+    ///
+    /// Return false when there's no valid link at `idx`.
+    fn open_link(
+        &mut self,
+        idx: usize,
+        stop_on_pipe: bool,
+        compounds: &mut Vec<Compound<'s>>,
+    ) -> bool {
+        if self.link.is_some() {
+            return false; // no link in a link
+        }
+        let rest = &self.src[idx..];
+        let (open, image) = if rest.starts_with("![") {
+            (idx + 1, true)
+        } else if rest.starts_with('[') {
+            (idx, false)
+        } else {
+            return false;
+        };
+        match link_parts(self.src, open, stop_on_pipe) {
+            Some((text_end, end, mut link)) => {
+                self.close_compound(idx, open + 1 - idx, compounds);
+                link.image = image;
+                self.link = Some(link);
+                self.link_text_end = text_end;
+                self.link_end = end;
+                self.link_has_content = false;
+                true
+            }
+            None => false,
+        }
+    }
+    /// close the text of the current link, whose closing bracket is at `idx`
+    fn close_link(
+        &mut self,
+        idx: usize,
+        compounds: &mut Vec<Compound<'s>>,
+    ) {
+        self.close_compound(idx, self.link_end - idx, compounds);
+        if !self.link_has_content {
+            // we keep an empty compound so that the link isn't lost
+            let mut compound = Compound::new(
+                self.src,
+                idx,
+                idx,
+                self.bold,
+                self.italic,
+                false,
+                self.strikeout,
+            );
+            compound.link = self.link;
+            compounds.push(compound);
+        }
+        self.link = None;
+    }
+    /// handle the run of underscores starting at `idx`, which opens or
+    /// closes an emphasis when the flanking rules allow it, and return
+    /// the index of its end
+    fn underscore_run(
+        &mut self,
+        idx: usize,
+        compounds: &mut Vec<Compound<'s>>,
+    ) -> usize {
+        let len = self.src[idx..].bytes().take_while(|&b| b == b'_').count();
+        let end = idx + len;
+        let (italic, bold) = match len {
+            1 => (true, false),
+            2 => (false, true),
+            3 => (true, true),
+            _ => {
+                return end; // a longer run is just text
+            }
+        };
+        let (can_open, can_close) = underscore_flanking(
+            self.src[..idx].chars().next_back(),
+            self.src[end..].chars().next(),
+        );
+        let all_open = (!italic || self.italic) && (!bold || self.bold);
+        let all_closed = (!italic || !self.italic) && (!bold || !self.bold);
+        if (all_open && can_close) || (all_closed && can_open) {
+            self.close_compound(idx, len, compounds);
+            self.italic ^= italic;
+            self.bold ^= bold;
+        }
+        end
+    }
     fn code_block_compound_from_idx(
         &self,
         idx: usize,
@@ -64,6 +201,7 @@ impl<'s> LineParser<'s> {
         let mut after_first_star = false;
         let mut after_first_tilde = false;
         let mut after_antislash = false;
+        let mut skip_until = 0; // used to skip the target part of links
 
         // self.idx tracks byte indices, but str::char_indices returns an
         // iterator over chars, which may be wider than one byte. So we need
@@ -71,6 +209,23 @@ impl<'s> LineParser<'s> {
         // before self.idx
         let chars_to_skip = self.src[..self.idx].chars().count();
         for (idx, char) in self.src.char_indices().skip(chars_to_skip) {
+            if idx < skip_until {
+                continue;
+            }
+            if self.link.is_some() && idx == self.link_text_end {
+                if after_first_star {
+                    // there was only one star
+                    self.close_compound(idx - 1, 1, &mut compounds);
+                    self.italic ^= true;
+                }
+                after_first_star = false;
+                after_first_tilde = false;
+                after_antislash = false;
+                self.code = false;
+                skip_until = self.link_end;
+                self.close_link(idx, &mut compounds);
+                continue;
+            }
             if self.code {
                 // only one thing matters: whether we're closing the inline code
                 if char == '`' {
@@ -86,14 +241,14 @@ impl<'s> LineParser<'s> {
             if after_antislash {
                 after_antislash = false;
                 match char {
-                    '*' | '~' | '|' | '`' => {
-                        self.close_compound(idx - 1, 1, &mut compounds);
-                        continue;
-                    }
                     '\\' => {
                         self.close_compound(idx, 1, &mut compounds);
                         continue;
                     }
+                    c if escape::is_escapable(c) => {
+                        self.close_compound(idx - 1, 1, &mut compounds);
+                        continue;
+                    }
                     _ => {} // we don't escape at all normal chars
                 }
             } else if char == '\\' {
@@ -101,6 +256,18 @@ impl<'s> LineParser<'s> {
                 continue;
             }
 
+            if char == '_' && self.underscore_emphasis {
+                if after_first_star {
+                    // there was only one star, just before the underscore
+                    self.close_compound(idx - 1, 1, &mut compounds);
+                    self.italic ^= true;
+                    after_first_star = false;
+                }
+                after_first_tilde = false;
+                skip_until = self.underscore_run(idx, &mut compounds);
+                continue;
+            }
+
             if after_first_star {
                 match char {
                     '*' => {
@@ -114,7 +281,7 @@ impl<'s> LineParser<'s> {
                         // we don't know yet if it's one or two tildes
                         self.italic ^= true;
                     }
-                    '|' if stop_on_pipe => {
+                    '|' if stop_on_pipe && self.is_cell_end(idx) => {
                         self.close_compound(idx - 1, 1, &mut compounds);
                         return compounds;
                     }
@@ -125,9 +292,11 @@ impl<'s> LineParser<'s> {
                     }
                     _ => {
                         // there was only one star
-                        // Note that we don't handle a tag just after a star (except in code)
+                        // Note that we don't handle a tag just after a star (except in code
+                        // and links)
                         self.close_compound(idx - 1, 1, &mut compounds);
                         self.italic ^= true;
+                        self.open_link(idx, stop_on_pipe, &mut compounds);
                     }
                 }
                 after_first_star = false;
@@ -142,12 +311,13 @@ impl<'s> LineParser<'s> {
                         self.close_compound(idx - 1, 2, &mut compounds);
                         self.strikeout ^= true;
                     }
-                    '|' if stop_on_pipe => {
+                    '|' if stop_on_pipe && self.is_cell_end(idx) => {
                         self.close_compound(idx - 1, 1, &mut compounds);
                         return compounds;
                     }
                     _ => {
                         // there was only one tilde, which means nothing
+                        self.open_link(idx, stop_on_pipe, &mut compounds);
                     }
                 }
                 after_first_tilde = false;
@@ -160,7 +330,7 @@ impl<'s> LineParser<'s> {
                     '~' => {
                         after_first_tilde = true;
                     }
-                    '|' if stop_on_pipe => {
+                    '|' if stop_on_pipe && self.is_cell_end(idx) => {
                         self.close_compound(idx, 0, &mut compounds);
                         return compounds;
                     }
@@ -168,6 +338,9 @@ impl<'s> LineParser<'s> {
                         self.close_compound(idx, 1, &mut compounds);
                         self.code = true;
                     }
+                    '[' | '!' => {
+                        self.open_link(idx, stop_on_pipe, &mut compounds);
+                    }
                     _ => {}
                 }
             }
@@ -186,30 +359,31 @@ impl<'s> LineParser<'s> {
         let mut cells = Vec::new();
         while self.idx < self.src.len() {
             self.idx += 1;
-            let style = if let Some((depth, consumed, ordered_index)) =
-                list_item_prefix(&self.src[self.idx..])
-            {
+            let (style, quote_depth) = if starts_with_line_escape(&self.src[self.idx..]) {
+                self.idx += 1;
+                (CompositeStyle::Paragraph, 0)
+            } else {
+                let (quote_depth, consumed) = quote_prefix(&self.src[self.idx..], u8::MAX);
                 self.idx += consumed;
-                if let Some(index) = ordered_index {
-                    CompositeStyle::OrderedListItem {
-                        level: depth,
-                        index,
-                    }
+                if let Some((consumed, style)) = list_item_prefix(&self.src[self.idx..]) {
+                    self.idx += consumed;
+                    (style, quote_depth)
+                } else if quote_depth > 0 {
+                    (CompositeStyle::Quote, quote_depth)
                 } else {
-                    CompositeStyle::ListItem(depth)
+                    (CompositeStyle::Paragraph, 0)
                 }
-            } else if self.src[self.idx..].starts_with("> ") {
-                self.idx += 2;
-                CompositeStyle::Quote
-            } else {
-                CompositeStyle::Paragraph
             };
             self.bold = false;
             self.italic = false;
             self.code = false;
             self.strikeout = false;
             let compounds = self.parse_compounds(true);
-            let mut composite = Composite { style, compounds };
+            let mut composite = Composite {
+                style,
+                compounds,
+                quote_depth,
+            };
             composite.trim_spaces();
             cells.push(composite);
         }
@@ -222,23 +396,88 @@ impl<'s> LineParser<'s> {
         Composite {
             style: CompositeStyle::Paragraph,
             compounds: self.parse_compounds(false),
+            quote_depth: 0,
         }
     }
     /// should be called when the line must be interpreted as a code part,
     /// for example between code fences
-    pub fn as_code(mut self) -> Line<'s> {
-        if self.src.starts_with("```") {
-            self.idx = 3;
+    pub fn as_code(self) -> Line<'s> {
+        self.quoted_code(0)
+    }
+    /// interpret the line as a code part of a code block which is in
+    /// quotes, up to the given depth, whose markers aren't part of the code
+    pub(crate) fn quoted_code(
+        mut self,
+        max_quote_depth: u8,
+    ) -> Line<'s> {
+        let (quote_depth, start) = quote_prefix(self.src, max_quote_depth);
+        let mut line = if self.src[start..].starts_with("```") {
+            self.idx = start + 3;
             Line::new_code_fence(self.parse_compounds(false))
         } else {
-            Line::new_code(self.code_block_compound_from_idx(0))
-        }
+            Line::new_code(self.code_block_compound_from_idx(start))
+        };
+        set_quote_depth(&mut line, quote_depth);
+        line
     }
     pub fn line(mut self) -> Line<'s> {
         self.parse_line()
     }
+    /// if the line is indented text, which isn't a list item nor another
+    /// kind of block, parse it as the continuation of the list item of
+    /// this level
+    ///
+    /// Choosing the item by the indentation of the line is up to the caller.
+    pub(crate) fn parse_list_continuation(
+        &mut self,
+        level: u8,
+    ) -> Option<Line<'s>> {
+        let content = self.src.trim_start_matches([' ', '\t']);
+        let indent = self.src.len() - content.len();
+        if indent == 0 || content.is_empty() || list_item_prefix(self.src).is_some() {
+            return None;
+        }
+        if list_item_prefix(content).is_some() {
+            return None; // a deeper item, or code
+        }
+        let probe = LineParser::from(content).line();
+        if !matches!(&probe, Line::Normal(c) if c.style == CompositeStyle::Paragraph) {
+            return None;
+        }
+        self.idx = indent;
+        if starts_with_line_escape(content) {
+            self.idx += 1;
+        }
+        Some(Line::Normal(Composite {
+            style: CompositeStyle::ListItemContinuation(level),
+            compounds: self.parse_compounds(false),
+            quote_depth: 0,
+        }))
+    }
     pub(crate) fn parse_line(&mut self) -> Line<'s> {
-        if self.src.starts_with('|') {
+        let (quote_depth, start) = quote_prefix(self.src, u8::MAX);
+        if quote_depth == 0 {
+            return self.parse_block(0, false);
+        }
+        let mut line = self.parse_block(start, true);
+        if let Line::Normal(composite) = &mut line {
+            if composite.style == CompositeStyle::Paragraph {
+                composite.style = CompositeStyle::Quote;
+            }
+        }
+        set_quote_depth(&mut line, quote_depth);
+        line
+    }
+    /// parse the line from `start`, which is after the quote markers, if any.
+    ///
+    /// Tables and horizontal rules aren't read inside quotes.
+    fn parse_block(
+        &mut self,
+        start: usize,
+        quoted: bool,
+    ) -> Line<'s> {
+        let src = &self.src[start..];
+        if src.starts_with('|') && !quoted {
             let tr = TableRow {
                 cells: self.parse_cells(),
             };
@@ -247,39 +486,42 @@ impl<'s> LineParser<'s> {
                 None => Line::TableRow(tr),
             };
         }
-        if self.src.starts_with("    ") {
-            return Line::new_code(self.code_block_compound_from_idx(4));
+        if src.starts_with("    ") {
+            return Line::new_code(self.code_block_compound_from_idx(start + 4));
         }
-        if self.src.starts_with('\t') {
-            return Line::new_code(self.code_block_compound_from_idx(1));
+        if src.starts_with('\t') {
+            return Line::new_code(self.code_block_compound_from_idx(start + 1));
         }
-        if let Some((depth, consumed, ordered_index)) = list_item_prefix(self.src) {
-            self.idx = consumed;
-            let compounds = self.parse_compounds(false);
-            return if let Some(index) = ordered_index {
-                Line::new_ordered_list_item(depth, index, compounds)
-            } else {
-                Line::new_list_item(depth, compounds)
-            };
-        }
-        if self.src == ">" {
-            return Line::new_quote(Vec::new());
+        self.idx = start;
+        if starts_with_line_escape(src) {
+            self.idx += 1;
+            return Line::new_paragraph(self.parse_compounds(false));
         }
-        if self.src.starts_with("> ") {
-            self.idx = 2;
-            return Line::new_quote(self.parse_compounds(false));
+        if let Some((consumed, style)) = list_item_prefix(src) {
+            self.idx += consumed;
+            let compounds = self.parse_compounds(false);
+            return Line::Normal(Composite {
+                style,
+                compounds,
+                quote_depth: 0,
+            });
         }
-        if self.src.starts_with("```") {
-            self.idx = 3;
+        if src.starts_with("```") {
+            self.idx += 3;
             return Line::new_code_fence(self.parse_compounds(false));
         }
-        let header_level = header_level(self.src);
+        let header_level = header_level(src);
         if header_level > 0 {
-            self.idx = header_level + 1;
+            self.idx += header_level + 1;
+            let content = strip_closing_hashes(&self.src[self.idx..]);
+            self.src = &self.src[..self.idx + content.len()];
             return Line::new_header(header_level as u8, self.parse_compounds(false));
         }
+        if is_star_rule(src) && !quoted {
+            return Line::HorizontalRule;
+        }
         let compounds = self.parse_compounds(false);
-        if compounds_are_rule(&compounds) {
+        if compounds_are_rule(&compounds) && !quoted {
             Line::HorizontalRule
         } else {
             Line::new_paragraph(compounds)
@@ -289,6 +531,198 @@ impl<'s> LineParser<'s> {
 
 const DASH: u8 = 45;
 
+/// Read the link whose text starts with the `[` at index `open`, that
+/// is either an inline link (`[text](url)`, `[text](url "title")`,
+/// `[text](<url>)`) or a reference link (`[text][label]`, `[text][]`).
+///
+/// Return the index of the `]` closing the text, the index just after
+/// the end of the link, and the link (whose url is empty for reference
+/// links, until they're resolved).
+fn link_parts(
+    src: &str,
+    open: usize,
+    stop_on_pipe: bool,
+) -> Option<(usize, usize, Link<'_>)> {
+    let bytes = src.as_bytes();
+    // we look for the closing bracket, ignoring escaped chars and inline code
+    let mut depth = 0;
+    let mut i = open;
+    let text_end = loop {
+        match bytes.get(i)? {
+            b'\\' if cfg!(feature = "escaping") => {
+                i += 1;
+            }
+            b'`' => {
+                i += 1 + src[i + 1..].find('`')?;
+            }
+            b'|' if stop_on_pipe => {
+                return None;
+            }
+            b'[' => {
+                depth += 1;
+            }
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+    match bytes.get(text_end + 1) {
+        Some(b'(') => {
+            let (url, title, i) = link_destination(src, text_end + 2, stop_on_pipe)?;
+            if bytes.get(i) != Some(&b')') {
+                return None;
+            }
+            let mut link = Link::new(url);
+            link.title = title;
+            Some((text_end, i + 1, link))
+        }
+        Some(b'[') => {
+            let label_start = text_end + 2;
+            let label_len = src[label_start..].find(']')?;
+            let mut label = &src[label_start..label_start + label_len];
+            if label.contains('[') || (stop_on_pipe && label.contains('|')) {
+                return None;
+            }
+            if label.is_empty() {
+                // collapsed reference: the text is the label
+                label = &src[open + 1..text_end];
+            }
+            Some((
+                text_end,
+                label_start + label_len + 1,
+                Link::new("").reference(label),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Read a link destination (`url` or `<url>`) optionally followed
+/// by a title (`"title"`, `'title'` or `(title)`), spaces before
+/// being skipped.
+///
+/// Return the url, the title, and the index after the title and the
+/// spaces following it.
+fn link_destination(
+    src: &str,
+    start: usize,
+    stop_on_pipe: bool,
+) -> Option<(&str, Option<&str>, usize)> {
+    let bytes = src.as_bytes();
+    let skip_spaces = |mut i: usize| {
+        while bytes.get(i) == Some(&b' ') {
+            i += 1;
+        }
+        i
+    };
+    let url_start = skip_spaces(start);
+    let (url, mut i) = if bytes.get(url_start) == Some(&b'<') {
+        let url_end = url_start + 1 + src[url_start + 1..].find('>')?;
+        (&src[url_start + 1..url_end], url_end + 1)
+    } else {
+        let mut depth = 0;
+        let mut i = url_start;
+        loop {
+            match bytes.get(i) {
+                None | Some(b' ') => break,
+                Some(b'|') if stop_on_pipe => return None,
+                Some(b'(') => depth += 1,
+                Some(b')') if depth == 0 => break,
+                Some(b')') => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        (&src[url_start..i], i)
+    };
+    i = skip_spaces(i);
+    let mut title = None;
+    let closing = match bytes.get(i) {
+        Some(b'"') => Some(b'"'),
+        Some(b'\'') => Some(b'\''),
+        Some(b'(') => Some(b')'),
+        _ => None,
+    };
+    if let Some(closing) = closing {
+        let title_start = i + 1;
+        let title_len = bytes[title_start..].iter().position(|&b| b == closing)?;
+        title = Some(&src[title_start..title_start + title_len]);
+        i = skip_spaces(title_start + title_len + 1);
+    }
+    Some((url, title, i))
+}
+
+/// If the line is a link reference definition (like `[label]: url "title"`),
+/// return the label and the link it defines
+pub(crate) fn link_definition(src: &str) -> Option<(&str, Link<'_>)> {
+    let open = src.len() - src.trim_start_matches(' ').len();
+    if open > 3 || !src[open..].starts_with('[') {
+        return None;
+    }
+    let label_len = src[open + 1..].find(']')?;
+    let label = &src[open + 1..open + 1 + label_len];
+    let colon = open + label_len + 2;
+    if label.trim().is_empty() || label.contains('[') || src.as_bytes().get(colon) != Some(&b':') {
+        return None;
+    }
+    let (url, title, end) = link_destination(src, colon + 1, false)?;
+    if url.is_empty() || end != src.len() {
+        return None;
+    }
+    let mut link = Link::new(url);
+    link.title = title;
+    Some((label, link))
+}
+
+/// Tell whether two link labels match, which is the case when
+/// they only differ by case or whitespaces
+pub(crate) fn link_labels_match(
+    a: &str,
+    b: &str,
+) -> bool {
+    a.split_whitespace()
+        .map(str::to_lowercase)
+        .eq(b.split_whitespace().map(str::to_lowercase))
+}
+
+/// Tell whether the line starts with an antislash preventing the rest
+/// to be read as a header, a quote, a list item or a rule (like in
+/// `\# not a header` or `\1. not a list item`).
+///
+/// Other antislashes at the start of a line, like in `\1984`, are kept.
+fn starts_with_line_escape(s: &str) -> bool {
+    cfg!(feature = "escaping") && s.starts_with('\\') && escape::needs_line_start_escape(&s[1..])
+}
+
+/// Tell whether a run of underscores between `prev` and `next` (which
+/// are none at the line boundaries) can open an emphasis, and whether it
+/// can close one, following the CommonMark flanking rules which prevent
+/// emphasis inside words like `snake_case_names`.
+fn underscore_flanking(
+    prev: Option<char>,
+    next: Option<char>,
+) -> (bool, bool) {
+    let is_space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    let is_punct = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation());
+    let left_flanking = !is_space(next) && (!is_punct(next) || is_space(prev) || is_punct(prev));
+    let right_flanking = !is_space(prev) && (!is_punct(prev) || is_space(next) || is_punct(next));
+    (
+        left_flanking && (!right_flanking || is_punct(prev)),
+        right_flanking && (!left_flanking || is_punct(next)),
+    )
+}
+
+/// tell whether the line is a horizontal rule made of at least 3 stars
+/// (like `***`), which can't be confused with the underline of a header
+fn is_star_rule(src: &str) -> bool {
+    src.len() >= 3 && src.bytes().all(|b| b == b'*')
+}
+
 fn compounds_are_rule(compounds: &[Compound<'_>]) -> bool {
     if compounds.len() != 1 {
         return false;
@@ -306,10 +740,11 @@ fn compounds_are_rule(compounds: &[Compound<'_>]) -> bool {
 }
 
 /// If the line starts with a list item marker (0-3 leading spaces followed by
-/// `* `, `- `, `+ `, or `<digits>. `/`<digits>) `), return the depth, the
-/// number of bytes consumed by the whole prefix, and, for ordered markers,
-/// the index read from the source.
-fn list_item_prefix(s: &str) -> Option<(u8, usize, Option<u32>)> {
+/// `* `, `- `, `+ `, or `<digits>. `/`<digits>) `), return the number of bytes
+/// consumed by the whole prefix and the style of the item.
+///
+/// An unordered marker followed by `[ ]` or `[x]` makes a task list item.
+fn list_item_prefix(s: &str) -> Option<(usize, CompositeStyle)> {
     let bytes = s.as_bytes();
     let mut depth = 0;
     while depth < bytes.len() && bytes[depth] == b' ' {
@@ -322,7 +757,14 @@ fn list_item_prefix(s: &str) -> Option<(u8, usize, Option<u32>)> {
 
     // unordered
     if rest.starts_with("* ") || rest.starts_with("- ") || rest.starts_with("+ ") {
-        return Some((depth as u8, depth + 2, None));
+        let level = depth as u8;
+        return Some(match task_marker(&rest[2..]) {
+            Some((consumed, checked)) => (
+                depth + 2 + consumed,
+                CompositeStyle::TaskListItem { level, checked },
+            ),
+            None => (depth + 2, CompositeStyle::ListItem(level)),
+        });
     }
 
     // ordered
@@ -335,17 +777,99 @@ fn list_item_prefix(s: &str) -> Option<(u8, usize, Option<u32>)> {
     }
     if matches!(rest.as_bytes().get(i), Some(b'.') | Some(b')')) {
         let index = rest[..i].parse().unwrap_or(1);
+        let style = CompositeStyle::OrderedListItem {
+            level: depth as u8,
+            index,
+        };
         if i + 1 == rest.len() {
-            return Some((depth as u8, depth + i + 1, Some(index)));
+            return Some((depth + i + 1, style));
         }
         if rest.as_bytes()[i + 1] == b' ' {
-            return Some((depth as u8, depth + i + 2, Some(index)));
+            return Some((depth + i + 2, style));
         }
     }
 
     None
 }
 
+/// If the line starts with a list item marker, return the column of the
+/// content of the item, which the indented lines continuing it reach
+pub(crate) fn list_item_content_column(s: &str) -> Option<usize> {
+    let (consumed, style) = list_item_prefix(s)?;
+    Some(match style {
+        // the task marker is part of the content
+        CompositeStyle::TaskListItem { level, .. } => usize::from(level) + 2,
+        // an ordered marker may end the line without its space
+        _ if !s[..consumed].ends_with(' ') => consumed + 1,
+        _ => consumed,
+    })
+}
+
+/// Return the width, in columns, of the spaces and tabs starting the
+/// line, a tab going to the next multiple of 4
+pub(crate) fn indentation(s: &str) -> usize {
+    let mut columns = 0;
+    for b in s.bytes() {
+        match b {
+            b' ' => columns += 1,
+            b'\t' => columns += 4 - columns % 4,
+            _ => break,
+        }
+    }
+    columns
+}
+
+/// If the line starts with quote markers (`>` followed by a space, another
+/// `>` or the end of the line), return their number, up to `max_depth`,
+/// and the number of bytes they take with the space following each of them
+fn quote_prefix(
+    s: &str,
+    max_depth: u8,
+) -> (u8, usize) {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut idx = 0;
+    while depth < max_depth
+        && bytes.get(idx) == Some(&b'>')
+        && matches!(bytes.get(idx + 1), None | Some(b' ') | Some(b'>'))
+    {
+        depth += 1;
+        idx += 1;
+        if bytes.get(idx) == Some(&b' ') {
+            idx += 1;
+        }
+    }
+    (depth, idx)
+}
+
+/// set the quote depth of the composite of a normal line or code fence
+fn set_quote_depth(
+    line: &mut Line<'_>,
+    quote_depth: u8,
+) {
+    if let Line::Normal(composite) | Line::CodeFence(composite) = line {
+        composite.quote_depth = quote_depth;
+    }
+}
+
+/// If the content of a list item starts with a task marker (`[ ]`, `[x]`
+/// or `[X]`, followed by a space or ending the line), return the number
+/// of bytes it takes and whether the task is checked
+fn task_marker(s: &str) -> Option<(usize, bool)> {
+    let checked = match s.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => {
+            return None;
+        }
+    };
+    match s.as_bytes().get(3) {
+        None => Some((3, checked)),
+        Some(b' ') => Some((4, checked)),
+        _ => None,
+    }
+}
+
 /// Tests of line parsing
 #[cfg(test)]
 mod tests {
@@ -499,6 +1023,170 @@ mod tests {
         );
     }
 
+    #[test]
+    fn links() {
+        assert_eq!(
+            Line::from("see [the *doc*](https://dystroy.org \"Doc\") for details"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("see "),
+                Compound::raw_str("the ").link(Link::new("https://dystroy.org").title("Doc")),
+                Compound::raw_str("doc")
+                    .italic()
+                    .link(Link::new("https://dystroy.org").title("Doc")),
+                Compound::raw_str(" for details"),
+            ])
+        );
+        assert_eq!(
+            Line::from("**[bold link](<a b.md>)**"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("bold link")
+                    .bold()
+                    .link(Link::new("a b.md")),
+            ])
+        );
+        assert_eq!(
+            Line::from("*[it](https://en.wikipedia.org/wiki/Rust_(language))*"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("it")
+                    .italic()
+                    .link(Link::new("https://en.wikipedia.org/wiki/Rust_(language)")),
+            ])
+        );
+        // not links
+        assert_eq!(
+            Line::from("[a] (b) [c](d `e)`"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("[a] (b) [c](d "),
+                Compound::raw_str("e)").code(),
+            ])
+        );
+        assert_eq!(
+            Line::from("`[a](b)`"),
+            Line::new_paragraph(vec![Compound::raw_str("[a](b)").code(),])
+        );
+    }
+
+    #[test]
+    fn reference_links() {
+        assert_eq!(
+            Line::from("[full][Some Ref], [collapsed][] and ![image][logo] but not [this] [one]"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("full").link(Link::new("").reference("Some Ref")),
+                Compound::raw_str(", "),
+                Compound::raw_str("collapsed").link(Link::new("").reference("collapsed")),
+                Compound::raw_str(" and "),
+                Compound::raw_str("image").link(Link::new_image("").reference("logo")),
+                Compound::raw_str(" but not [this] [one]"),
+            ])
+        );
+    }
+
+    #[test]
+    fn link_definitions() {
+        use crate::parser::link_definition;
+        assert_eq!(
+            link_definition("[ref]: https://dystroy.org"),
+            Some(("ref", Link::new("https://dystroy.org"))),
+        );
+        assert_eq!(
+            link_definition("   [Some Ref]:  <a b.md>  'A B' "),
+            Some(("Some Ref", Link::new("a b.md").title("A B"))),
+        );
+        assert_eq!(link_definition("[ref]:"), None);
+        assert_eq!(link_definition("[ref]: a b"), None);
+        assert_eq!(link_definition("[ref] : a"), None);
+        assert_eq!(link_definition("    [ref]: a"), None);
+    }
+
+    #[test]
+    fn images() {
+        assert_eq!(
+            Line::from("![a *logo*](img/logo.png 'Logo')!"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("a ").link(Link::new_image("img/logo.png").title("Logo")),
+                Compound::raw_str("logo")
+                    .italic()
+                    .link(Link::new_image("img/logo.png").title("Logo")),
+                Compound::raw_str("!"),
+            ])
+        );
+        // the image is kept even without alt text
+        assert_eq!(
+            Line::from("![](logo.png)"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("").link(Link::new_image("logo.png")),
+            ])
+        );
+    }
+
+    #[test]
+    fn link_in_table() {
+        assert_eq!(
+            Line::from("|[a](b)|[c|d](e)|"),
+            Line::new_table_row(vec![
+                Composite {
+                    style: CompositeStyle::Paragraph,
+                    compounds: vec![Compound::raw_str("a").link(Link::new("b")),],
+                    quote_depth: 0,
+                },
+                Composite {
+                    style: CompositeStyle::Paragraph,
+                    compounds: vec![Compound::raw_str("[c"),],
+                    quote_depth: 0,
+                },
+                Composite {
+                    style: CompositeStyle::Paragraph,
+                    compounds: vec![Compound::raw_str("d](e)"),],
+                    quote_depth: 0,
+                },
+            ])
+        );
+    }
+
+    #[cfg(feature = "escaping")]
+    #[test]
+    fn escaped_link() {
+        assert_eq!(
+            Line::from("\\[a](b)"),
+            Line::new_paragraph(vec![Compound::raw_str("[a](b)"),])
+        );
+    }
+
+    #[cfg(feature = "escaping")]
+    #[test]
+    fn escaped_line_start() {
+        assert_eq!(
+            Line::from("\\# not a header"),
+            Line::new_paragraph(vec![Compound::raw_str("# not a header")])
+        );
+        assert_eq!(
+            Line::from("\\2. not *a* list"),
+            Line::new_paragraph(vec![
+                Compound::raw_str("2. not "),
+                Compound::raw_str("a").italic(),
+                Compound::raw_str(" list"),
+            ])
+        );
+        assert_eq!(
+            Line::from("|\\- a|\\> b|"),
+            Line::TableRow(TableRow {
+                cells: vec![
+                    Composite::from(vec![Compound::raw_str("- a")]),
+                    Composite::from(vec![Compound::raw_str("> b")]),
+                ],
+            })
+        );
+        // the antislash is kept when there's nothing to escape
+        assert_eq!(
+            Line::from("\\1984"),
+            Line::new_paragraph(vec![Compound::raw_str("\\1984")])
+        );
+        assert_eq!(
+            Line::from("\\#hashtag"),
+            Line::new_paragraph(vec![Compound::raw_str("\\#hashtag")])
+        );
+    }
+
     #[test]
     fn code_fence() {
         assert_eq!(Line::from("```"), Line::new_code_fence(vec![]));
@@ -524,6 +1212,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn header_with_closing_hashes() {
+        assert_eq!(
+            Line::from("## a **title** ##  "),
+            Line::new_header(
+                2,
+                vec![Compound::raw_str("a "), Compound::raw_str("title").bold(),]
+            )
+        );
+        assert_eq!(
+            Line::from("# C#"),
+            Line::new_header(1, vec![Compound::raw_str("C#"),])
+        );
+        assert_eq!(
+            Line::from("# issue # #"),
+            Line::new_header(1, vec![Compound::raw_str("issue #"),])
+        );
+        let line = Line::from("# title #");
+        assert_eq!(line.span(), Some(Span::new(0, 2, 7)));
+    }
+
     #[test]
     fn list_item() {
         assert_eq!(
@@ -614,6 +1323,8 @@ mod tests {
     #[test]
     fn horizontal_rule() {
         assert_eq!(Line::from("----------"), Line::HorizontalRule,);
+        assert_eq!(Line::from("***"), Line::HorizontalRule,);
+        assert_eq!(Line::from("*****"), Line::HorizontalRule,);
     }
 
     #[test]
@@ -639,18 +1350,22 @@ mod tests {
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("bla"),],
+                    quote_depth: 0,
                 },
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("italic").italic(),],
+                    quote_depth: 0,
                 },
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("hi!"),],
+                    quote_depth: 0,
                 },
                 Composite {
                     style: CompositeStyle::Quote,
                     compounds: vec![Compound::raw_str("some quote"),],
+                    quote_depth: 1,
                 }
             ])
         );
@@ -664,14 +1379,17 @@ mod tests {
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("安"),],
+                    quote_depth: 0,
                 },
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("安"),],
+                    quote_depth: 0,
                 },
                 Composite {
                     style: CompositeStyle::Paragraph,
                     compounds: vec![Compound::raw_str("安"),],
+                    quote_depth: 0,
                 },
             ])
         );
@@ -689,4 +1407,178 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn underscore_emphasis() {
+        let options = Options::default().underscore_emphasis(true);
+        let parse = |md| Composite::from_inline_with_options(md, options);
+        assert_eq!(
+            parse("some _italic_, __bold__ and ___both___"),
+            Composite::from_inline("some *italic*, **bold** and ***both***"),
+        );
+        assert_eq!(
+            parse("__bold *and italic*__ _it **and bold**_"),
+            Composite::from_inline("**bold *and italic*** *it **and bold***"),
+        );
+        // no emphasis inside words
+        assert_eq!(
+            parse("call snake_case_names or x__init__y _ a_"),
+            Composite::raw_str("call snake_case_names or x__init__y _ a_"),
+        );
+        assert_eq!(
+            parse("(_a_) _b_. foo_bar_"),
+            Composite::from_inline("(*a*) *b*. foo_bar_"),
+        );
+        assert_eq!(parse("____"), Composite::raw_str("____"));
+        // without the option, underscores are just text
+        assert_eq!(
+            Composite::from_inline("_a_ __b__"),
+            Composite::raw_str("_a_ __b__"),
+        );
+    }
+
+    #[cfg(feature = "escaping")]
+    #[test]
+    fn escaped_underscore() {
+        let options = Options::default().underscore_emphasis(true);
+        let composite = Composite::from_inline_with_options(r"\_not italic_", options);
+        assert!(composite.compounds.iter().all(|c| !c.italic));
+        let content: String = composite.compounds.iter().map(|c| c.as_str()).collect();
+        assert_eq!(content, "_not italic_");
+        // the antislash is an escape even without the option
+        let composite = Composite::from_inline(r"snake\_case");
+        let content: String = composite.compounds.iter().map(|c| c.as_str()).collect();
+        assert_eq!(content, "snake_case");
+    }
+
+    #[test]
+    fn task_list_items() {
+        assert_eq!(
+            Line::from("- [ ] to do"),
+            Line::new_task_list_item(0, false, vec![Compound::raw_str("to do")]),
+        );
+        assert_eq!(
+            Line::from("  * [X] *done*"),
+            Line::new_task_list_item(2, true, vec![Compound::raw_str("done").italic()]),
+        );
+        assert_eq!(
+            Line::from("+ [x]"),
+            Line::new_task_list_item(0, true, vec![])
+        );
+        // not task markers
+        assert_eq!(
+            Line::from("- [x]y"),
+            Line::new_list_item(0, vec![Compound::raw_str("[x]y")]),
+        );
+        assert_eq!(
+            Line::from("- [y] z"),
+            Line::new_list_item(0, vec![Compound::raw_str("[y] z")]),
+        );
+        assert_eq!(
+            Line::from("1. [ ] a"),
+            Line::new_ordered_list_item(0, 1, vec![Compound::raw_str("[ ] a")]),
+        );
+        assert_eq!(
+            Line::from("|- [ ] a|b|"),
+            Line::new_table_row(vec![
+                Composite {
+                    style: CompositeStyle::TaskListItem {
+                        level: 0,
+                        checked: false,
+                    },
+                    compounds: vec![Compound::raw_str("a")],
+                    quote_depth: 0,
+                },
+                Composite::from(vec![Compound::raw_str("b")]),
+            ]),
+        );
+    }
+
+    #[test]
+    fn nested_quotes() {
+        let quoted = |quote_depth, line: Line<'static>| match line {
+            Line::Normal(composite) => Line::Normal(Composite {
+                quote_depth,
+                ..composite
+            }),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            Line::from(">> nested *quote*"),
+            quoted(
+                2,
+                Line::new_quote(vec![
+                    Compound::raw_str("nested "),
+                    Compound::raw_str("quote").italic(),
+                ])
+            ),
+        );
+        assert_eq!(
+            Line::from("> > nested"),
+            quoted(2, Line::new_quote(vec![Compound::raw_str("nested")])),
+        );
+        assert_eq!(
+            Line::from("> - list in quote"),
+            quoted(
+                1,
+                Line::new_list_item(0, vec![Compound::raw_str("list in quote")])
+            ),
+        );
+        assert_eq!(
+            Line::from(">   2. item"),
+            quoted(
+                1,
+                Line::new_ordered_list_item(2, 2, vec![Compound::raw_str("item")])
+            ),
+        );
+        assert_eq!(
+            Line::from("> # header in quote"),
+            quoted(
+                1,
+                Line::new_header(1, vec![Compound::raw_str("header in quote")])
+            ),
+        );
+        assert_eq!(
+            Line::from(">>     code"),
+            quoted(2, Line::new_code(Compound::raw_str("code"))),
+        );
+        assert_eq!(Line::from(">>"), quoted(2, Line::new_quote(vec![])));
+        assert_eq!(Line::from(">").quote_depth(), 1);
+        // only the paragraphs of quotes have the Quote style
+        let plain = Composite::from_inline("x");
+        assert!(!plain.is_quote() && !plain.is_quoted());
+        match Line::from("> - item") {
+            Line::Normal(item) => assert!(!item.is_quote() && item.is_quoted()),
+            line => panic!("unexpected line: {:?}", line),
+        }
+        match Line::from("> text") {
+            Line::Normal(text) => assert!(text.is_quote() && text.is_quoted()),
+            line => panic!("unexpected line: {:?}", line),
+        }
+        // no table nor rule in quotes
+        assert_eq!(
+            Line::from("> ---"),
+            quoted(1, Line::new_quote(vec![Compound::raw_str("---")])),
+        );
+        assert_eq!(Line::from("> |a|").quote_depth(), 1);
+        // not quotes
+        assert_eq!(Line::from(">a"), Line::raw_str(">a"));
+        assert_eq!(Line::from("- > a").quote_depth(), 0);
+        // the spans are relative to the whole line
+        assert_eq!(Line::from(">> # title").span(), Some(Span::new(0, 5, 10)));
+    }
+
+    #[test]
+    fn quotes_in_cells() {
+        match Line::from("|>> a|> - b|c|") {
+            Line::TableRow(row) => {
+                assert_eq!(row.cells[0].style, CompositeStyle::Quote);
+                assert_eq!(row.cells[0].quote_depth, 2);
+                assert_eq!(row.cells[1].style, CompositeStyle::ListItem(0));
+                assert_eq!(row.cells[1].quote_depth, 1);
+                assert_eq!(row.cells[2].quote_depth, 0);
+            }
+            line => panic!("unexpected line: {:?}", line),
+        }
+    }
 }
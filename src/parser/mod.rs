@@ -1,10 +1,14 @@
+mod autolinks;
 mod line_parser;
 mod options;
+mod stream_parser;
 mod text_parser;
 
+pub(crate) use autolinks::detect_autolinks;
 pub use {
     line_parser::*,
     options::*,
+    stream_parser::*,
     text_parser::*,
 };
 
@@ -25,7 +29,8 @@ fn indented_code_between_fences() {
                 Line::new_paragraph(vec![Compound::raw_str("outside")]),
                 Line::new_code(Compound::raw_str("a")),
                 Line::new_code(Compound::raw_str("    b")),
-            ]
+            ],
+            code_langs: vec![(1, "code")],
         },
     );
 }
@@ -51,7 +56,8 @@ fn test_clean() {
                 Line::from("bla bla bla"),
                 Line::from("* item 1"),
                 Line::from("* item 2"),
-            ]
+            ],
+            code_langs: Vec::new(),
         },
     );
 }
@@ -68,7 +74,8 @@ fn test_inline_code_continuation() {
     assert_eq!(
         parse_text(md, options),
         Text {
-            lines: vec![Line::from("bla bla `code"), Line::from("again` bla"),]
+            lines: vec![Line::from("bla bla `code"), Line::from("again` bla"),],
+            code_langs: Vec::new(),
         },
     );
     // With continuation
@@ -78,7 +85,8 @@ fn test_inline_code_continuation() {
     assert_eq!(
         parse_text(md, options),
         Text {
-            lines: vec![Line::from("bla bla `code`"), Line::from("`again` bla"),]
+            lines: vec![Line::from("bla bla `code`"), Line::from("`again` bla"),],
+            code_langs: Vec::new(),
         },
     );
 }
@@ -1,5 +1,5 @@
 /// Markdown parsing options
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Options {
     /// Remove one or several superfluous levels of indentations
     ///
@@ -19,7 +19,7 @@ pub struct Options {
     ///         Line::from("bla bla bla"),
     ///         Line::from("* item 1"),
     ///         Line::from("* item 2"),
-    ///     ]},
+    ///     ], code_langs: vec![] },
     /// );
     /// ```
     ///
@@ -29,6 +29,15 @@ pub struct Options {
     pub continue_bold: bool,
     pub continue_strikeout: bool,
     pub keep_code_fences: bool,
+    /// Make links of the autolinks (like `<https://dystroy.org>`), of the
+    /// bare urls (like `https://dystroy.org` or `www.dystroy.org`) and of
+    /// the email addresses found in the text, except in code
+    pub autolinks: bool,
+    /// Read `_italic_` and `__bold__` like with stars, except inside
+    /// words (like in `snake_case_names`).
+    ///
+    /// `\_` is an escaped underscore whether this option is set or not.
+    pub underscore_emphasis: bool,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -41,6 +50,8 @@ impl Default for Options {
             continue_bold: false,
             continue_strikeout: false,
             keep_code_fences: false,
+            autolinks: false,
+            underscore_emphasis: false,
         }
     }
 }
@@ -98,4 +109,18 @@ impl Options {
         self.keep_code_fences = value;
         self
     }
+    pub fn autolinks(
+        mut self,
+        value: bool,
+    ) -> Self {
+        self.autolinks = value;
+        self
+    }
+    pub fn underscore_emphasis(
+        mut self,
+        value: bool,
+    ) -> Self {
+        self.underscore_emphasis = value;
+        self
+    }
 }
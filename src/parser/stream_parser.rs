@@ -0,0 +1,264 @@
+use {
+    super::text_parser::{
+        LinesParser,
+        ParsedLine,
+        is_setext_content,
+        locate_spans,
+        resolve_references,
+    },
+    crate::*,
+    std::{
+        collections::VecDeque,
+        io::{
+            self,
+            BufRead,
+        },
+    },
+};
+
+/// A parser receiving markdown in chunks, as they arrive, and giving
+/// the lines as soon as they're finished.
+///
+/// The produced lines are the same than when parsing the whole text,
+/// with two exceptions:
+/// * reference links are resolved only with the definitions
+///   which were received before
+/// * the `clean_indentations` option is ignored, as it needs the
+///   whole text
+///
+/// The spans of the compounds are relative to the whole stream.
+///
+/// A paragraph line is given only when the next line is received, as
+/// this one may be the underline making it a header.
+///
+/// ```
+/// use minimad::{*, parser::StreamParser};
+///
+/// let mut parser = StreamParser::new(Options::default());
+/// assert!(parser.push("# Tit").is_empty());
+/// let lines = parser.push("le\n* some **bo");
+/// assert_eq!(lines, vec![Line::from("# Title").into_owned()]);
+/// assert!(parser.push("ld**").is_empty());
+/// assert_eq!(parser.finish(), vec![Line::from("* some **bold**").into_owned()]);
+/// ```
+pub struct StreamParser {
+    pending: String, // the start of a line whose end wasn't received
+    consumed: usize, // the number of bytes before the pending line
+    line_idx: usize,
+    lines_parser: LinesParser,
+    options: Options,
+    definitions: Vec<(String, OwnedLink)>,
+    held: Option<OwnedLine>, // a paragraph which may be a setext header
+}
+
+impl StreamParser {
+    pub fn new(options: Options) -> Self {
+        Self {
+            pending: String::new(),
+            consumed: 0,
+            line_idx: 0,
+            lines_parser: LinesParser::new(options),
+            options,
+            definitions: Vec::new(),
+            held: None,
+        }
+    }
+    /// receive a chunk of markdown and return the lines it finishes
+    pub fn push(
+        &mut self,
+        chunk: &str,
+    ) -> Vec<OwnedLine> {
+        self.pending.push_str(chunk);
+        let pending = std::mem::take(&mut self.pending);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while let Some(len) = pending[start..].find('\n') {
+            let md_line = &pending[start..start + len];
+            let md_line = md_line.strip_suffix('\r').unwrap_or(md_line);
+            self.parse_line(md_line, self.consumed + start, &mut lines);
+            start += len + 1;
+        }
+        self.consumed += start;
+        self.pending = pending;
+        self.pending.drain(..start);
+        lines
+    }
+    /// parse the last line, if it wasn't terminated by a newline.
+    ///
+    /// The parser can be used again after, for a new text.
+    pub fn finish(&mut self) -> Vec<OwnedLine> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut lines = Vec::new();
+        if !pending.is_empty() {
+            self.parse_line(&pending, self.consumed, &mut lines);
+        }
+        lines.extend(self.held.take());
+        *self = Self::new(self.options);
+        lines
+    }
+    fn parse_line(
+        &mut self,
+        md_line: &str,
+        offset: usize,
+        lines: &mut Vec<OwnedLine>,
+    ) {
+        let line_idx = self.line_idx;
+        self.line_idx += 1;
+        let parsed = self.lines_parser.parse(md_line);
+        if let ParsedLine::SetextUnderline(level) = parsed {
+            if let Some(OwnedLine::Normal(mut composite)) = self.held.take() {
+                composite.style = CompositeStyle::Header(level);
+                lines.push(OwnedLine::Normal(composite));
+            }
+            return;
+        }
+        lines.extend(self.held.take());
+        match parsed {
+            ParsedLine::Line(mut line) => {
+                locate_spans(&mut line, line_idx, offset);
+                if !self.definitions.is_empty() {
+                    let definitions: Vec<(&str, Link<'_>)> = self
+                        .definitions
+                        .iter()
+                        .map(|(label, link)| (label.as_str(), link.as_link()))
+                        .collect();
+                    resolve_references(std::slice::from_mut(&mut line), &definitions);
+                }
+                if self.options.autolinks {
+                    parser::detect_autolinks(std::slice::from_mut(&mut line));
+                }
+                if is_setext_content(&line) {
+                    self.held = Some(line.into_owned());
+                } else {
+                    lines.push(line.into_owned());
+                }
+            }
+            ParsedLine::Definition(label, link) => {
+                self.definitions
+                    .push((label.to_string(), link.into_owned()));
+            }
+            ParsedLine::SetextUnderline(_) | ParsedLine::Skipped(_) => {}
+        }
+    }
+}
+
+/// An iterator over the lines parsed from a reader
+pub struct ReaderLines<R: BufRead> {
+    reader: R,
+    parser: StreamParser,
+    ready: VecDeque<OwnedLine>,
+    buffer: String,
+    done: bool,
+}
+
+impl<R: BufRead> ReaderLines<R> {
+    pub fn new(
+        reader: R,
+        options: Options,
+    ) -> Self {
+        Self {
+            reader,
+            parser: StreamParser::new(options),
+            ready: VecDeque::new(),
+            buffer: String::new(),
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ReaderLines<R> {
+    type Item = io::Result<OwnedLine>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.ready.pop_front() {
+                return Some(Ok(line));
+            }
+            if self.done {
+                return None;
+            }
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => {
+                    self.done = true;
+                    self.ready.extend(self.parser.finish());
+                }
+                Ok(_) => {
+                    self.ready.extend(self.parser.push(&self.buffer));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Parse the markdown given by a reader, line after line
+pub fn parse_reader<R: BufRead>(
+    reader: R,
+    options: Options,
+) -> ReaderLines<R> {
+    ReaderLines::new(reader, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::parser::StreamParser,
+    };
+
+    const MD: &str = r#"# Title
+Some **bold
+text** and a [link][ref].
+
+[ref]: https://dystroy.org
+Then a [resolved][ref] link.
+1. a
+1. b
+```
+code
+```
+|a|b|
+|-|-|
+Setext *title*
+===
+* end
+  and its *continuation*"#;
+
+    #[test]
+    fn same_lines_as_whole_parsing() {
+        let options = Options::default().continue_bold(true);
+        let text = parse_text(MD, options);
+        // we cut the markdown at every possible place
+        for cut in (0..MD.len()).filter(|&i| MD.is_char_boundary(i)) {
+            let mut parser = StreamParser::new(options);
+            let mut lines = parser.push(&MD[..cut]);
+            lines.extend(parser.push(&MD[cut..]));
+            lines.extend(parser.finish());
+            assert_eq!(lines.len(), text.lines.len());
+            for (idx, (line, expected)) in lines.iter().zip(&text.lines).enumerate() {
+                if idx == 2 {
+                    // the definition comes after this line
+                    assert_eq!(line.as_line().composites()[0].links()[0].url, "");
+                    continue;
+                }
+                assert_eq!(&line.as_line(), expected);
+                assert_eq!(line.span(), expected.span());
+            }
+        }
+    }
+
+    #[test]
+    fn read_lines() {
+        let lines: Vec<OwnedLine> = parse_reader(MD.as_bytes(), Options::default())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(
+            lines[6],
+            Line::new_ordered_list_item(0, 2, vec![Compound::raw_str("b")]).into_owned()
+        );
+    }
+}
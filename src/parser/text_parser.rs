@@ -6,82 +6,409 @@ pub fn parse(
     options: Options,
 ) -> Text<'_> {
     if options.clean_indentations {
-        parse_lines(clean::lines(md).into_iter(), options)
+        parse_lines(clean::lines(md).into_iter(), Some(md), options)
     } else {
-        parse_lines(md.lines(), options)
+        parse_lines(md.lines(), Some(md), options)
     }
 }
 
-/// Parse lines
+/// Parse lines.
+///
+/// When the lines are parts of a source string, this source should be
+/// given so that the spans of the compounds are relative to it.
 pub(crate) fn parse_lines<'s, I>(
     md_lines: I,
+    source: Option<&str>,
     options: Options,
 ) -> Text<'s>
 where
     I: Iterator<Item = &'s str>,
 {
+    let mut positions = LinePositions {
+        source,
+        counted_bytes: 0,
+        line_idx: 0,
+    };
+    let mut lines_parser = LinesParser::new(options);
     let mut lines = Vec::new();
-    let mut between_fences = false;
-    let mut continue_code = false;
-    let mut continue_italic = false;
-    let mut continue_bold = false;
-    let mut continue_strikeout = false;
-    for md_line in md_lines {
-        let mut line_parser = parser::LineParser::from(md_line);
-        let line = if between_fences {
-            continue_code = false;
-            continue_italic = false;
-            continue_bold = false;
-            continue_strikeout = false;
-            line_parser.as_code()
+    let mut code_langs = Vec::new();
+    let mut link_definitions = Vec::new();
+    for (iter_idx, md_line) in md_lines.enumerate() {
+        let (line_idx, offset) = positions.locate(md_line, iter_idx);
+        match lines_parser.parse(md_line) {
+            ParsedLine::Line(mut line) => {
+                locate_spans(&mut line, line_idx, offset);
+                lines.push(line);
+            }
+            ParsedLine::Definition(label, link) => {
+                link_definitions.push((label, link));
+            }
+            ParsedLine::SetextUnderline(level) => {
+                if let Some(Line::Normal(composite)) = lines.last_mut() {
+                    composite.style = CompositeStyle::Header(level);
+                }
+            }
+            ParsedLine::Skipped(Some(lang)) => {
+                code_langs.push((lines.len(), lang));
+            }
+            ParsedLine::Skipped(None) => {
+                // an empty block has no first line
+                if code_langs
+                    .last()
+                    .is_some_and(|&(idx, _)| idx == lines.len())
+                {
+                    code_langs.pop();
+                }
+            }
+        }
+    }
+    resolve_references(&mut lines, &link_definitions);
+    if options.autolinks {
+        parser::detect_autolinks(&mut lines);
+    }
+    Text { lines, code_langs }
+}
+
+/// What a markdown line gives when parsed as part of a text
+pub(crate) enum ParsedLine<'s> {
+    Line(Line<'s>),
+    Definition(&'s str, Link<'s>), // a link reference definition
+    SetextUnderline(u8),           // makes the previous line a header of this level
+    Skipped(Option<&'s str>), // a code fence which isn't kept, with the language of an opening one
+}
+
+/// tell whether the line is a paragraph which a setext underline
+/// (`===` or `---`) would turn into a header
+pub(crate) fn is_setext_content(line: &Line<'_>) -> bool {
+    match line {
+        Line::Normal(Composite {
+            style: CompositeStyle::Paragraph,
+            compounds,
+            ..
+        }) => compounds.iter().any(|c| !is_blank(c.src)),
+        _ => false,
+    }
+}
+
+/// tell whether the line is the text of a paragraph, a quote or a list
+/// item, which the next line may continue, so that a link definition
+/// can't start there
+pub(crate) fn is_paragraph_text(line: &Line<'_>) -> bool {
+    match line {
+        Line::Normal(composite) if !composite.is_code() => {
+            !matches!(composite.style, CompositeStyle::Header(_))
+                && composite.compounds.iter().any(|c| !is_blank(c.src))
+        }
+        _ => false,
+    }
+}
+
+/// The parser of the successive lines of a text, keeping the state
+/// which goes from one line to the next one: whether we're between
+/// code fences, the styles continued on next line, whether the
+/// previous line may be the content of a setext header, and the runs
+/// of ordered list items being renumbered.
+///
+/// Reference links aren't resolved and autolinks aren't detected
+/// at this level.
+pub(crate) struct LinesParser {
+    options: Options,
+    between_fences: bool,
+    fence_quote_depth: u8, // the quote depth of the opening code fence
+    continue_code: bool,
+    continue_italic: bool,
+    continue_bold: bool,
+    continue_strikeout: bool,
+    after_setext_content: bool,
+    after_paragraph_text: bool,
+    open_items: Vec<(u8, usize)>, // level and content column of the items the next lines may continue
+    after_blank: bool,            // whether there was a blank line since the list item
+    ordered_runs: OrderedRuns,
+}
+
+impl LinesParser {
+    pub(crate) fn new(options: Options) -> Self {
+        Self {
+            options,
+            between_fences: false,
+            fence_quote_depth: 0,
+            continue_code: false,
+            continue_italic: false,
+            continue_bold: false,
+            continue_strikeout: false,
+            after_setext_content: false,
+            after_paragraph_text: false,
+            open_items: Vec::new(),
+            after_blank: false,
+            ordered_runs: OrderedRuns::default(),
+        }
+    }
+    pub(crate) fn parse<'s>(
+        &mut self,
+        md_line: &'s str,
+    ) -> ParsedLine<'s> {
+        let options = self.options;
+        let after_setext_content = std::mem::replace(&mut self.after_setext_content, false);
+        let after_paragraph_text = std::mem::replace(&mut self.after_paragraph_text, false);
+        if !self.between_fences {
+            if after_setext_content {
+                if let Some(level) = setext_level(md_line) {
+                    return ParsedLine::SetextUnderline(level);
+                }
+            }
+            // a definition can't interrupt a paragraph, even a lazy line
+            if !after_paragraph_text {
+                if let Some((label, link)) = parser::link_definition(md_line) {
+                    return ParsedLine::Definition(label, link);
+                }
+            }
+        }
+        let mut line_parser = parser::LineParser::from(md_line).options(options);
+        let mut item_code = false;
+        let mut line = if self.between_fences {
+            self.continue_code = false;
+            self.continue_italic = false;
+            self.continue_bold = false;
+            self.continue_strikeout = false;
+            line_parser.quoted_code(self.fence_quote_depth)
         } else {
-            if continue_code {
+            if self.continue_code {
                 line_parser.code = true;
             }
-            if continue_italic {
+            if self.continue_italic {
                 line_parser.italic = true;
             }
-            if continue_bold {
+            if self.continue_bold {
                 line_parser.bold = true;
             }
-            if continue_strikeout {
+            if self.continue_strikeout {
                 line_parser.strikeout = true;
             }
-            let line = line_parser.parse_line();
-            continue_code = options.continue_inline_code && line_parser.code;
-            continue_italic = options.continue_italic && line_parser.italic;
-            continue_bold = options.continue_bold && line_parser.bold;
-            continue_strikeout = options.continue_strikeout && line_parser.strikeout;
+            let continuation = match self.reached_item(md_line) {
+                Some((_, column))
+                    if self.after_blank && parser::indentation(md_line) >= column + 4 =>
+                {
+                    // indented code inside the item
+                    item_code = true;
+                    None
+                }
+                Some((level, _)) => line_parser.parse_list_continuation(level),
+                // a lazy line, less indented than the item content
+                None if !self.after_blank => self
+                    .open_items
+                    .last()
+                    .and_then(|&(level, _)| line_parser.parse_list_continuation(level)),
+                None => None,
+            };
+            let line = match continuation {
+                Some(line) => line,
+                None => line_parser.parse_line(),
+            };
+            self.continue_code = options.continue_inline_code && line_parser.code;
+            self.continue_italic = options.continue_italic && line_parser.italic;
+            self.continue_bold = options.continue_bold && line_parser.bold;
+            self.continue_strikeout = options.continue_strikeout && line_parser.strikeout;
             line
         };
-        match line {
-            Line::CodeFence(..) => {
-                between_fences = !between_fences;
-                if options.keep_code_fences {
-                    lines.push(line);
+        if let Line::CodeFence(composite) = &line {
+            self.between_fences = !self.between_fences;
+            self.fence_quote_depth = composite.quote_depth;
+            if !options.keep_code_fences {
+                let lang = if self.between_fences {
+                    composite
+                        .compounds
+                        .first()
+                        .map(|c| c.src)
+                        .filter(|lang| !lang.is_empty())
+                } else {
+                    None
+                };
+                return ParsedLine::Skipped(lang);
+            }
+        }
+        self.follow_list(md_line, &mut line, item_code);
+        self.ordered_runs.fix_index(&mut line);
+        self.after_setext_content = !self.between_fences && is_setext_content(&line);
+        self.after_paragraph_text = !self.between_fences && is_paragraph_text(&line);
+        ParsedLine::Line(line)
+    }
+    /// return the level and content column of the deepest open list
+    /// item whose content column the indentation of the line reaches
+    fn reached_item(
+        &self,
+        md_line: &str,
+    ) -> Option<(u8, usize)> {
+        let indent = parser::indentation(md_line);
+        self.open_items
+            .iter()
+            .rev()
+            .find(|(_, column)| *column <= indent)
+            .copied()
+    }
+    /// keep track of the list items the next lines may continue, and
+    /// make a paragraph directly following a list item (a "lazy" line)
+    /// a continuation of the deepest item
+    fn follow_list(
+        &mut self,
+        md_line: &str,
+        line: &mut Line<'_>,
+        item_code: bool,
+    ) {
+        if line.is_blank() {
+            self.after_blank = true;
+            return;
+        }
+        if item_code && line.is_code() {
+            return; // the items stay open after their code
+        }
+        if let Line::Normal(composite) = line {
+            match composite.style {
+                _ if composite.quote_depth > 0 => {}
+                CompositeStyle::ListItem(level)
+                | CompositeStyle::TaskListItem { level, .. }
+                | CompositeStyle::OrderedListItem { level, .. } => {
+                    self.open_items.retain(|&(l, _)| l < level);
+                    if let Some(column) = parser::list_item_content_column(md_line) {
+                        self.open_items.push((level, column));
+                    }
+                    self.after_blank = false;
+                    return;
+                }
+                CompositeStyle::ListItemContinuation(level) => {
+                    // the deeper items are closed
+                    self.open_items.retain(|&(l, _)| l <= level);
+                    self.after_blank = false;
+                    return;
                 }
+                CompositeStyle::Paragraph if !self.after_blank => {
+                    if let Some(&(level, _)) = self.open_items.last() {
+                        composite.style = CompositeStyle::ListItemContinuation(level);
+                        return;
+                    }
+                }
+                _ => {}
             }
-            _ => {
-                lines.push(line);
+        }
+        self.open_items.clear();
+        self.after_blank = false;
+    }
+}
+
+/// Computes the positions, in the source, of the lines given to the parser
+struct LinePositions<'a> {
+    source: Option<&'a str>,
+    counted_bytes: usize, // the part of the source whose newlines are counted
+    line_idx: usize,
+}
+
+impl LinePositions<'_> {
+    /// return the index of the line in the source and its byte offset,
+    /// or just the index in the iteration when the line isn't part of
+    /// the source
+    fn locate(
+        &mut self,
+        md_line: &str,
+        iter_idx: usize,
+    ) -> (usize, usize) {
+        if let Some(source) = self.source {
+            let offset = (md_line.as_ptr() as usize).wrapping_sub(source.as_ptr() as usize);
+            if offset >= self.counted_bytes && offset + md_line.len() <= source.len() {
+                self.line_idx += source.as_bytes()[self.counted_bytes..offset]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count();
+                self.counted_bytes = offset;
+                return (self.line_idx, offset);
+            }
+        }
+        (iter_idx, 0)
+    }
+}
+
+/// Make the spans of the compounds of a freshly parsed line, which
+/// are relative to the line, relative to the source
+pub(crate) fn locate_spans(
+    line: &mut Line<'_>,
+    line_idx: usize,
+    offset: usize,
+) {
+    for composite in line.composites_mut() {
+        for compound in &mut composite.compounds {
+            if let Some(span) = &mut compound.span {
+                span.line = line_idx;
+                span.start += offset;
+                span.end += offset;
+            }
+        }
+    }
+}
+
+/// Give their url and title to the reference links (`[text][label]`)
+/// whose label is defined in the text.
+///
+/// When there are several definitions for the same label, the first
+/// one is used.
+pub(crate) fn resolve_references<'s>(
+    lines: &mut [Line<'s>],
+    definitions: &[(&str, Link<'s>)],
+) {
+    if definitions.is_empty() {
+        return;
+    }
+    let resolve = |composite: &mut Composite<'s>| {
+        for compound in &mut composite.compounds {
+            let link = match &mut compound.link {
+                Some(link) => link,
+                None => continue,
+            };
+            if let Some(label) = link.reference {
+                let definition = definitions
+                    .iter()
+                    .find(|(defined, _)| parser::link_labels_match(defined, label));
+                if let Some((_, definition)) = definition {
+                    link.url = definition.url;
+                    link.title = definition.title;
+                }
             }
         }
+    };
+    for line in lines {
+        line.composites_mut().iter_mut().for_each(resolve);
     }
-    fix_ordered_indexes(&mut lines);
-    Text { lines }
 }
 
-/// Renumber the indexes of consecutive OrderedListItem lines so that ordered
-/// lists read as a text have consistent numbering, starting from the index of
-/// the first item in each run.
-fn fix_ordered_indexes(lines: &mut Vec<Line<'_>>) {
-    #[derive(Default)]
-    struct Seq {
-        start: u32,
-        count: u32,
-        active: bool,
-    }
-    let mut seqs: Vec<Seq> = Vec::new();
-    for line in lines.iter_mut() {
+/// The runs of consecutive OrderedListItem lines, used to renumber
+/// their indexes so that ordered lists read as a text have consistent
+/// numbering, starting from the index of the first item in each run.
+///
+/// Blank lines end the runs only when they're not followed by the
+/// continuation of an item.
+#[derive(Default)]
+struct OrderedRuns {
+    seqs: Vec<Seq>,    // one per level
+    after_blank: bool, // whether blank lines were met since the last item
+}
+
+#[derive(Default)]
+struct Seq {
+    start: u32,
+    count: u32,
+    active: bool,
+}
+
+impl OrderedRuns {
+    /// renumber the line if it's an ordered list item, or
+    /// end the runs if it's another kind of line
+    fn fix_index(
+        &mut self,
+        line: &mut Line<'_>,
+    ) {
+        if line.is_blank() {
+            self.after_blank = true;
+            return;
+        }
+        let after_blank = std::mem::replace(&mut self.after_blank, false);
+        let seqs = &mut self.seqs;
         if let Line::Normal(Composite {
             style: CompositeStyle::OrderedListItem { level, index },
             ..
@@ -91,6 +418,11 @@ fn fix_ordered_indexes(lines: &mut Vec<Line<'_>>) {
             while seqs.len() <= level {
                 seqs.push(Seq::default());
             }
+            if after_blank {
+                for s in seqs.iter_mut() {
+                    s.active = false;
+                }
+            }
             if seqs[level].active {
                 seqs[level].count += 1;
             } else {
@@ -104,7 +436,22 @@ fn fix_ordered_indexes(lines: &mut Vec<Line<'_>>) {
             }
             *index = seqs[level].start + seqs[level].count - 1;
         } else {
-            for s in seqs.iter_mut() {
+            // a nested unordered item (for example a task of a checklist
+            // under an ordered item), or the continuation of an item,
+            // doesn't end the shallower runs
+            let level = match line {
+                Line::Normal(Composite {
+                    style:
+                        CompositeStyle::ListItem(level) | CompositeStyle::TaskListItem { level, .. },
+                    ..
+                }) => *level as usize,
+                Line::Normal(Composite {
+                    style: CompositeStyle::ListItemContinuation(level),
+                    ..
+                }) => *level as usize + 1,
+                _ => 0,
+            };
+            for s in seqs.iter_mut().skip(level) {
                 s.active = false;
             }
         }
@@ -159,6 +506,238 @@ mod tests {
         assert_ordered(&text.lines[3], 0, 1);
     }
 
+    #[test]
+    fn resolves_reference_links() {
+        let md = r#"A [first][ref] and a [Second ref][] link.
+| ![an image][logo] |
+```
+[ref]: not a definition
+```
+[ref]: https://dystroy.org "Dystroy"
+[second  REF]: <second.md>
+[logo]: logo.png
+[unused]: https://example.com"#;
+        let text = parse_text(md, Options::default());
+        assert_eq!(
+            text,
+            Text {
+                lines: vec![
+                    Line::new_paragraph(vec![
+                        Compound::raw_str("A "),
+                        Compound::raw_str("first").link(
+                            Link::new("https://dystroy.org")
+                                .title("Dystroy")
+                                .reference("ref")
+                        ),
+                        Compound::raw_str(" and a "),
+                        Compound::raw_str("Second ref")
+                            .link(Link::new("second.md").reference("Second ref")),
+                        Compound::raw_str(" link."),
+                    ]),
+                    Line::new_table_row(vec![Composite::from(vec![
+                        Compound::raw_str("an image")
+                            .link(Link::new_image("logo.png").reference("logo")),
+                    ])]),
+                    Line::new_code(Compound::raw_str("[ref]: not a definition")),
+                ],
+                code_langs: Vec::new(),
+            }
+        );
+        assert!(text.unresolved_references().is_empty());
+    }
+
+    #[test]
+    fn code_langs_without_fences() {
+        let md = "a\n```rust\nlet a;\n```\n```json\n```\n> ```toml\n> [b]\n> ```";
+        let text = parse_text(md, Options::default());
+        assert_eq!(text.lines.len(), 3);
+        assert_eq!(text.code_langs, vec![(1, "rust"), (2, "toml")]);
+        assert_eq!(text.code_lang(2), Some("toml"));
+        assert_eq!(
+            text.to_markdown(),
+            "a\n```rust\nlet a;\n```\n> ```toml\n> [b]\n> ```\n",
+        );
+        assert_eq!(parse_text(&text.to_markdown(), Options::default()), text);
+        let text = parse_text(md, Options::default().keep_code_fences(true));
+        assert!(text.code_langs.is_empty());
+    }
+
+    #[test]
+    fn definitions_dont_interrupt_paragraphs() {
+        let md = "a [link][ref]\n[ref]: a.md\n* item\nlazy line\n[ref]: b.md\n\n[ref]: c.md";
+        let text = parse_text(md, Options::default());
+        let continuation = |src| {
+            Line::Normal(Composite {
+                style: CompositeStyle::ListItemContinuation(0),
+                compounds: vec![Compound::raw_str(src)],
+                quote_depth: 0,
+            })
+        };
+        assert_eq!(
+            text.lines,
+            vec![
+                Line::new_paragraph(vec![
+                    Compound::raw_str("a "),
+                    Compound::raw_str("link").link(Link::new("c.md").reference("ref")),
+                ]),
+                Line::new_paragraph(vec![Compound::raw_str("[ref]: a.md")]),
+                Line::new_list_item(0, vec![Compound::raw_str("item")]),
+                continuation("lazy line"),
+                continuation("[ref]: b.md"),
+                Line::new_paragraph(vec![]),
+            ]
+        );
+        // the serializer keeps them apart
+        let written = text.to_markdown();
+        assert_eq!(
+            written,
+            "a [link][ref]\n[ref]: a.md\n* item\n  lazy line\n  [ref]: b.md\n\n[ref]:c.md\n",
+        );
+        assert_eq!(parse_text(&written, Options::default()), text);
+        // an empty line is added when the text ends with a paragraph
+        let mut text = text;
+        text.lines.pop();
+        assert!(
+            text.to_markdown()
+                .ends_with("  [ref]: b.md\n\n[ref]:c.md\n")
+        );
+        #[cfg(feature = "escaping")]
+        {
+            let text = parse_text(r"\[ref]: a.md", Options::default());
+            assert_eq!(text.lines.len(), 1);
+            assert_eq!(text.to_markdown(), "\\[ref]: a.md\n");
+        }
+    }
+
+    #[test]
+    fn reports_unresolved_references() {
+        let md = "[a][missing] [b][ref] [c][missing]\n\n[ref]: b.md";
+        let text = parse_text(md, Options::default());
+        assert_eq!(text.lines.len(), 2);
+        assert_eq!(text.unresolved_references(), vec!["missing"]);
+    }
+
+    #[test]
+    fn detects_autolinks_when_asked() {
+        let md = "See <https://dystroy.org> or *www.dystroy.org*, not `https://x.y`.";
+        assert_eq!(
+            parse_text(md, Options::default()).lines[0],
+            Line::new_paragraph(vec![
+                Compound::raw_str("See <https://dystroy.org> or "),
+                Compound::raw_str("www.dystroy.org").italic(),
+                Compound::raw_str(", not "),
+                Compound::raw_str("https://x.y").code(),
+                Compound::raw_str("."),
+            ])
+        );
+        assert_eq!(
+            parse_text(md, Options::default().autolinks(true)).lines[0],
+            Line::new_paragraph(vec![
+                Compound::raw_str("See "),
+                Compound::raw_str("https://dystroy.org")
+                    .link(Link::new("https://dystroy.org").autolink()),
+                Compound::raw_str(" or "),
+                Compound::raw_str("www.dystroy.org")
+                    .italic()
+                    .link(Link::new("www.dystroy.org").autolink()),
+                Compound::raw_str(", not "),
+                Compound::raw_str("https://x.y").code(),
+                Compound::raw_str("."),
+            ])
+        );
+        let text = parse_text(
+            "|mail|me@dystroy.org|\n    code: https://dystroy.org",
+            Options::default().autolinks(true),
+        );
+        assert_eq!(
+            text.lines[0],
+            Line::new_table_row(vec![
+                Composite::from(vec![Compound::raw_str("mail")]),
+                Composite::from(vec![
+                    Compound::raw_str("me@dystroy.org")
+                        .link(Link::new("me@dystroy.org").autolink()),
+                ]),
+            ])
+        );
+        assert_eq!(
+            text.lines[1],
+            Line::new_code(Compound::raw_str("code: https://dystroy.org"))
+        );
+    }
+
+    #[test]
+    fn computes_spans() {
+        let md = "# Title\n\n* some **bold**\n|a|`b`|\n```\n    code\n```";
+        let text = parse_text(md, Options::default());
+        assert_eq!(text.lines.len(), 5);
+        let spans: Vec<_> = text.lines.iter().map(Line::span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Some(Span::new(0, 2, 7)),
+                None,
+                Some(Span::new(2, 11, 22)),
+                Some(Span::new(3, 26, 30)),
+                Some(Span::new(5, 37, 45)),
+            ]
+        );
+        assert_eq!(&md[spans[2].unwrap().range()], "some **bold");
+        let compounds = &text.lines[2].composites()[0].compounds;
+        assert_eq!(&md[compounds[1].span.unwrap().range()], "bold");
+        let cells = text.lines[3].composites();
+        assert_eq!(cells[1].span(), Some(Span::new(3, 29, 30)));
+        // with indentation cleaning, spans still point in the original source
+        let md = "\n    text\n    *more* text\n";
+        let text = parse_text(md, Options::default().clean_indentations(true));
+        let compound = &text.lines[1].composites()[0].compounds[1];
+        assert_eq!(compound.span, Some(Span::new(2, 20, 25)));
+        assert_eq!(&md[compound.span.unwrap().range()], " text");
+    }
+
+    #[test]
+    fn reads_setext_headers() {
+        let md = r#"Main *title*
+===
+A sub title
+  ---
+---
+* not a title
+---
+```
+in code
+---
+```
+## closing hashes ##"#;
+        let text = parse_text(md, Options::default());
+        assert_eq!(
+            text,
+            Text {
+                lines: vec![
+                    Line::new_header(
+                        1,
+                        vec![
+                            Compound::raw_str("Main "),
+                            Compound::raw_str("title").italic(),
+                        ]
+                    ),
+                    Line::new_header(2, vec![Compound::raw_str("A sub title")]),
+                    Line::HorizontalRule,
+                    Line::new_list_item(0, vec![Compound::raw_str("not a title")]),
+                    Line::HorizontalRule,
+                    Line::new_code(Compound::raw_str("in code")),
+                    Line::new_code(Compound::raw_str("---")),
+                    Line::new_header(2, vec![Compound::raw_str("closing hashes")]),
+                ],
+                code_langs: Vec::new(),
+            }
+        );
+        assert_eq!(text.lines[0].span(), Some(Span::new(0, 0, 11)));
+        // an underline after an empty line is a rule or a paragraph
+        let text = parse_text("a\n\n---\n===", Options::default());
+        assert_eq!(text.lines[2], Line::HorizontalRule);
+        assert_eq!(text.lines[3], Line::from("==="));
+    }
+
     #[test]
     fn handles_nested_ordered_lists() {
         let text = parse_text("1. a\n 1. inner\n 2. inner\n2. b", Options::default());
@@ -168,4 +747,147 @@ mod tests {
         assert_ordered(&text.lines[2], 1, 2);
         assert_ordered(&text.lines[3], 0, 2);
     }
+
+    #[test]
+    fn renumbers_around_nested_tasks() {
+        let text = parse_text(
+            "1. a\n   - [ ] b\n   - [x] c\n1. d\n- [ ] e\n1. f",
+            Options::default(),
+        );
+        assert_eq!(text.lines.len(), 6);
+        assert_ordered(&text.lines[0], 0, 1);
+        assert_ordered(&text.lines[3], 0, 2);
+        // a task at the same level ends the run
+        assert_ordered(&text.lines[5], 0, 1);
+    }
+
+    #[test]
+    fn reads_code_fences_in_quotes() {
+        let md = "> ```rust\n> let a = *b;\n>\n> ```\n> after";
+        let text = parse_text(md, Options::default().keep_code_fences(true));
+        assert_eq!(text.lines.len(), 5);
+        assert!(text.lines.iter().all(|line| line.quote_depth() == 1));
+        assert_eq!(text.lines[0].code_fence_lang(), Some("rust"));
+        assert!(text.lines[1].is_code());
+        assert_eq!(
+            text.lines[1].composites()[0].compounds[0].src,
+            "let a = *b;"
+        );
+        assert!(text.lines[2].is_code());
+        assert!(matches!(text.lines[3], Line::CodeFence(_)));
+        assert!(!text.lines[4].is_code());
+    }
+
+    #[test]
+    fn reads_list_item_continuations() {
+        let md = "* a\n  more *a*\nlazy a\n  * b\n\n      second paragraph of b\n\nnot in list\n    code";
+        let text = parse_text(md, Options::default());
+        let continuation = |level, compounds| {
+            Line::Normal(Composite {
+                style: CompositeStyle::ListItemContinuation(level),
+                compounds,
+                quote_depth: 0,
+            })
+        };
+        assert_eq!(
+            text.lines,
+            vec![
+                Line::new_list_item(0, vec![Compound::raw_str("a")]),
+                continuation(
+                    0,
+                    vec![Compound::raw_str("more "), Compound::raw_str("a").italic()]
+                ),
+                continuation(0, vec![Compound::raw_str("lazy a")]),
+                Line::new_list_item(2, vec![Compound::raw_str("b")]),
+                Line::new_paragraph(vec![]),
+                continuation(2, vec![Compound::raw_str("second paragraph of b")]),
+                Line::new_paragraph(vec![]),
+                Line::new_paragraph(vec![Compound::raw_str("not in list")]),
+                Line::new_code(Compound::raw_str("code")),
+            ]
+        );
+        let span = text.lines[5].span().unwrap();
+        assert_eq!(&md[span.range()], "second paragraph of b");
+        // other blocks end the list
+        let text = parse_text("* a\n# b\nc\n* d\n> e", Options::default());
+        assert!(!text.lines.iter().any(Line::is_list_item_continuation));
+    }
+
+    #[test]
+    fn reads_code_in_list_items() {
+        let md = "* item\n\n        let x = 1;\n\n  more about item\n\n      also code";
+        let text = parse_text(md, Options::default());
+        assert_eq!(text.lines.len(), 7);
+        assert_eq!(
+            text.lines[2],
+            Line::new_code(Compound::raw_str("    let x = 1;"))
+        );
+        assert!(text.lines[4].is_list_item_continuation());
+        assert_eq!(
+            text.lines[6],
+            Line::new_code(Compound::raw_str("  also code"))
+        );
+        assert_eq!(text.to_string(), format!("{md}\n"));
+        // without a blank line, it's a continuation of the paragraph
+        let text = parse_text("* item\n        more", Options::default());
+        assert!(text.lines[1].is_list_item_continuation());
+    }
+
+    #[test]
+    fn continues_the_item_reached_by_the_indentation() {
+        let md = "* a\n  * b\n\n  back to a\n\n    and to b?\n\n1. c\n\n   more c";
+        let text = parse_text(md, Options::default());
+        let levels: Vec<Option<u8>> = text
+            .lines
+            .iter()
+            .map(|line| match line {
+                Line::Normal(Composite {
+                    style: CompositeStyle::ListItemContinuation(level),
+                    ..
+                }) => Some(*level),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                None,
+                None,
+                None,
+                Some(0),
+                None,
+                Some(0),
+                None,
+                None,
+                None,
+                Some(0)
+            ],
+        );
+        // "back to a" closed b
+        assert_eq!(
+            text.lines[5],
+            Line::Normal(Composite {
+                style: CompositeStyle::ListItemContinuation(0),
+                compounds: vec![Compound::raw_str("and to b?")],
+                quote_depth: 0,
+            }),
+        );
+        let written = text.to_string();
+        assert!(written.starts_with("* a\n  * b\n\n  back to a\n\n  and to b?\n"));
+        assert!(written.ends_with("\n1. c\n\n   more c\n"));
+        assert_eq!(parse_text(&written, Options::default()), text);
+    }
+
+    #[test]
+    fn renumbers_across_continuations() {
+        let md = "1. a\n\n   more about a\n1. b\n   lazy\n1. c\n\n1. d";
+        let text = parse_text(md, Options::default());
+        assert_eq!(text.lines.len(), 8);
+        assert!(text.lines[2].is_list_item_continuation());
+        assert_ordered(&text.lines[3], 0, 2);
+        assert!(text.lines[4].is_list_item_continuation());
+        assert_ordered(&text.lines[5], 0, 3);
+        // a blank line followed by an item still ends the run
+        assert_ordered(&text.lines[7], 0, 1);
+    }
 }
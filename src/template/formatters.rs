@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// a function formatting the value of a placeholder, receiving the
+/// optional argument given after a colon (for example `1` in
+/// `${ratio|percent:1}`)
+pub type Formatter = fn(&str, Option<&str>) -> String;
+
+/// a registry of named formatters, applied by the template expanders to
+/// the values of placeholders like `${size|bytes}` or `${ratio|percent:1}`.
+///
+/// The registry also keeps the values formatted by the last expansion it
+/// was given to, which the expanded text borrows. They're replaced by the
+/// next expansion.
+///
+/// ```
+/// use minimad::*;
+///
+/// let template = TextTemplate::from("Size: ${size|bytes}, ${name|upper}");
+/// let mut formatters = Formatters::standard();
+/// let mut expander = template.expander_with_formatters(&mut formatters);
+/// expander.set("size", "2500000").set("name", "disk");
+/// assert_eq!(expander.expand().to_string(), "Size: 2.4 MiB, DISK\n");
+/// ```
+#[derive(Default)]
+pub struct Formatters {
+    formatters: Vec<(String, Formatter)>,
+    values: Vec<String>, // the values formatted for the last expansion
+}
+
+impl Clone for Formatters {
+    /// clone the registry, without the formatted values
+    fn clone(&self) -> Self {
+        Self {
+            formatters: self.formatters.clone(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Formatters {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_list()
+            .entries(self.formatters.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl Formatters {
+    /// build an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// build a registry with the `upper`, `lower`, `bytes` and
+    /// `percent` formatters
+    pub fn standard() -> Self {
+        let mut formatters = Self::new();
+        formatters
+            .add("upper", |s, _| s.to_uppercase())
+            .add("lower", |s, _| s.to_lowercase())
+            .add("bytes", format_bytes)
+            .add("percent", format_percent);
+        formatters
+    }
+    /// add a formatter, replacing the one with the same name, if any
+    pub fn add(
+        &mut self,
+        name: &str,
+        formatter: Formatter,
+    ) -> &mut Self {
+        self.formatters.retain(|(n, _)| n != name);
+        self.formatters.push((name.to_string(), formatter));
+        self
+    }
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Option<Formatter> {
+        self.formatters
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, formatter)| *formatter)
+    }
+    /// format the value with the formatter of the given name, or return
+    /// it unchanged if there's no such formatter
+    pub fn format(
+        &self,
+        name: &str,
+        arg: Option<&str>,
+        value: &str,
+    ) -> String {
+        match self.get(name) {
+            Some(formatter) => formatter(value, arg),
+            None => value.to_string(),
+        }
+    }
+    /// replace the kept values with the given ones, formatted, and
+    /// return them, in the same order
+    pub(crate) fn format_values<'v, I>(
+        &mut self,
+        values: I,
+    ) -> &[String]
+    where
+        I: Iterator<Item = (&'v str, Option<&'v str>, &'v str)>, // formatter, arg, value
+    {
+        self.values.clear();
+        for (name, arg, value) in values {
+            let value = self.format(name, arg, value);
+            self.values.push(value);
+        }
+        &self.values
+    }
+}
+
+fn decimals(arg: Option<&str>) -> usize {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or(1)
+}
+
+/// format a number of bytes with binary units, with the number of
+/// decimals given as argument (1 by default)
+fn format_bytes(
+    value: &str,
+    arg: Option<&str>,
+) -> String {
+    let mut size: f64 = match value.trim().parse::<u64>() {
+        Ok(bytes) => bytes as f64,
+        Err(_) => return value.to_string(),
+    };
+    for unit in &["B", "KiB", "MiB", "GiB", "TiB"] {
+        if size < 1024.0 || *unit == "TiB" {
+            if *unit == "B" {
+                return format!("{size} B");
+            }
+            return format!("{:.*} {}", decimals(arg), size, unit);
+        }
+        size /= 1024.0;
+    }
+    unreachable!()
+}
+
+/// format a ratio as a percentage, with the number of decimals given
+/// as argument (1 by default)
+fn format_percent(
+    value: &str,
+    arg: Option<&str>,
+) -> String {
+    match value.trim().parse::<f64>() {
+        Ok(ratio) => format!("{:.*}%", decimals(arg), ratio * 100.0),
+        Err(_) => value.to_string(),
+    }
+}
+
+#[test]
+fn standard_formatters() {
+    let formatters = Formatters::standard();
+    assert_eq!(formatters.format("bytes", None, "12"), "12 B");
+    assert_eq!(formatters.format("bytes", Some("2"), "1536"), "1.50 KiB");
+    assert_eq!(formatters.format("bytes", None, "big"), "big");
+    assert_eq!(formatters.format("percent", Some("0"), "0.256"), "26%");
+    assert_eq!(formatters.format("percent", None, "1"), "100.0%");
+    assert_eq!(formatters.format("lower", None, "ABC"), "abc");
+    assert_eq!(formatters.format("unknown", None, "ABC"), "ABC");
+}
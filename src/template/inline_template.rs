@@ -1,4 +1,10 @@
-use crate::Composite;
+use {
+    super::text_template::is_valid_name_char,
+    crate::{
+        Composite,
+        Options,
+    },
+};
 
 // There's an ergonomics limit here: https://stackoverflow.com/q/59306592/263525
 // It could probably be solved by defining 10 functions, each one with a different number of
@@ -10,7 +16,6 @@ use crate::Composite;
 struct Arg {
     compounds_idx: Vec<usize>, // indexes of the compounds the arg should fill
 }
-#[allow(dead_code)] // this isn't really dead code, it depends on the macro used
 impl Arg {
     fn add(
         &mut self,
@@ -25,41 +30,109 @@ impl Arg {
 /// It can be used to build a composite and insert parts not interpreted
 /// as markdown.
 ///
+/// Placeholders are either positional, like `$0` or `$12`, or named,
+/// like `${name}`.
 ///
 /// The [`mad_inline!`](macro.mad_inline.html) macro wraps the call to the `InlineTemplate` and
 /// is more convenient for most uses.
 #[derive(Debug, PartialEq, Eq)]
 pub struct InlineTemplate<'a> {
     composite: Composite<'a>,
-    args: [Arg; 10],
+    args: Vec<(usize, Arg)>,         // the positional args: $0, $1, etc.
+    named_args: Vec<(&'a str, Arg)>, // the args like ${name}
+    options: Options,                // used to parse the template and md values
+}
+
+/// read the placeholder starting with the `$` at `idx`, if any, and
+/// return it with the index of its end
+fn read_placeholder(
+    src: &str,
+    idx: usize,
+) -> Option<(Placeholder<'_>, usize)> {
+    let after_dollar = &src[idx + 1..];
+    if let Some(name) = after_dollar.strip_prefix('{') {
+        let name_len = name.find('}')?;
+        let name = &name[..name_len];
+        if !name.is_empty() && name.chars().all(is_valid_name_char) {
+            return Some((Placeholder::Named(name), idx + name_len + 3));
+        }
+        return None;
+    }
+    let digits_len = after_dollar
+        .bytes()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    let num = after_dollar[..digits_len].parse().ok()?;
+    Some((Placeholder::Positional(num), idx + digits_len + 1))
+}
+
+enum Placeholder<'a> {
+    Positional(usize),
+    Named(&'a str),
 }
 
 impl<'a> InlineTemplate<'a> {
-    /// build a template from a markdown string which may contain `$0` to `$9`
+    /// build a template from a markdown string which may contain
+    /// placeholders like `$0`, `$12` or `${name}`
     pub fn from(md: &'a str) -> InlineTemplate<'a> {
-        let mut composite = Composite::from_inline(md);
+        Self::with_options(md, Options::default())
+    }
+
+    /// build a template with specific parsing options, which also
+    /// apply to the values given to `apply_md` and `apply_named_md`
+    pub fn with_options(
+        md: &'a str,
+        options: Options,
+    ) -> InlineTemplate<'a> {
+        let mut composite = Composite::from_inline_with_options(md, options);
         let mut compounds = Vec::new();
-        let mut args: [Arg; 10] = Default::default();
+        let mut args: Vec<(usize, Arg)> = Vec::new();
+        let mut named_args: Vec<(&'a str, Arg)> = Vec::new();
         for compound in composite.compounds {
             // we iterate over the compounds of the template strings
-            // looking for the $i locus
-            let mut after_dollar = false;
+            // looking for the placeholders
+            let src = compound.as_str();
             let mut start = 0;
-            for (idx, char) in compound.as_str().char_indices() {
-                if after_dollar {
-                    if char.is_ascii_digit() {
-                        let num: u8 = (char as u8) - b'0';
-                        if start + 1 < idx {
-                            compounds.push(compound.sub(start, idx - 1));
-                        }
-                        start = idx + 1;
-                        args[usize::from(num)].compounds_idx.push(compounds.len());
-                        compounds.push(compound.sub(idx - 1, start)); // placeholder
+            let mut idx = 0;
+            while let Some(dollar_idx) = src[idx..].find('$') {
+                idx += dollar_idx;
+                let (placeholder, end) = match read_placeholder(src, idx) {
+                    Some(placeholder) => placeholder,
+                    None => {
+                        idx += 1;
+                        continue;
                     }
-                    after_dollar = false;
-                } else if char == '$' {
-                    after_dollar = true;
+                };
+                if start < idx {
+                    compounds.push(compound.sub(start, idx));
                 }
+                let arg = match placeholder {
+                    Placeholder::Positional(num) => {
+                        // args are stored sparsely as the index may be big
+                        let arg_idx = match args.iter().position(|(n, _)| *n == num) {
+                            Some(arg_idx) => arg_idx,
+                            None => {
+                                args.push((num, Arg::default()));
+                                args.len() - 1
+                            }
+                        };
+                        &mut args[arg_idx].1
+                    }
+                    Placeholder::Named(name) => {
+                        let arg_idx = match named_args.iter().position(|(n, _)| *n == name) {
+                            Some(arg_idx) => arg_idx,
+                            None => {
+                                named_args.push((name, Arg::default()));
+                                named_args.len() - 1
+                            }
+                        };
+                        &mut named_args[arg_idx].1
+                    }
+                };
+                arg.add(compounds.len());
+                compounds.push(compound.sub(idx, end)); // placeholder
+                start = end;
+                idx = end;
             }
             let tail = compound.tail(start);
             if !tail.is_empty() {
@@ -67,30 +140,128 @@ impl<'a> InlineTemplate<'a> {
             }
         }
         composite.compounds = compounds;
-        InlineTemplate { composite, args }
+        InlineTemplate {
+            composite,
+            args,
+            named_args,
+            options,
+        }
     }
 
     pub fn raw_composite(&self) -> Composite<'a> {
         self.composite.clone()
     }
 
-    pub fn apply(
+    fn fill(
         &self,
         composite: &mut Composite<'a>,
-        arg_idx: usize,
+        arg: Option<&Arg>,
         value: &'a str,
     ) {
-        if arg_idx > 9 {
-            return;
+        if let Some(arg) = arg {
+            for compound_idx in &arg.compounds_idx {
+                composite.compounds[*compound_idx].set_str(value);
+            }
         }
-        for compound_idx in &self.args[arg_idx].compounds_idx {
-            composite.compounds[*compound_idx].set_str(value.as_ref());
+    }
+
+    fn fill_md(
+        &self,
+        composite: &mut Composite<'a>,
+        arg: Option<&Arg>,
+        md: &'a str,
+    ) {
+        let arg = match arg {
+            Some(arg) => arg,
+            None => {
+                return;
+            }
+        };
+        let replacing_compounds = Composite::from_inline_with_options(md, self.options).compounds;
+        // each splice moves the following compounds
+        let mut inserted = 0;
+        for (filled, compound_idx) in arg.compounds_idx.iter().enumerate() {
+            let idx = compound_idx + inserted - filled;
+            composite
+                .compounds
+                .splice(idx..=idx, replacing_compounds.iter().cloned());
+            inserted += replacing_compounds.len();
         }
     }
+
+    /// fill the placeholders of the positional arg (e.g. `$2`) with
+    /// a value which isn't interpreted as markdown
+    pub fn apply(
+        &self,
+        composite: &mut Composite<'a>,
+        arg_idx: usize,
+        value: &'a str,
+    ) {
+        self.fill(composite, self.positional_arg(arg_idx), value);
+    }
+
+    /// fill the placeholders of the positional arg (e.g. `$2`) with
+    /// a value interpreted as markdown
+    ///
+    /// As the value may be made of several compounds, the compounds
+    /// following the placeholders move: they must be filled before.
+    pub fn apply_md(
+        &self,
+        composite: &mut Composite<'a>,
+        arg_idx: usize,
+        md: &'a str,
+    ) {
+        self.fill_md(composite, self.positional_arg(arg_idx), md);
+    }
+
+    fn positional_arg(
+        &self,
+        arg_idx: usize,
+    ) -> Option<&Arg> {
+        self.args
+            .iter()
+            .find(|(n, _)| *n == arg_idx)
+            .map(|(_, arg)| arg)
+    }
+
+    fn named_arg(
+        &self,
+        name: &str,
+    ) -> Option<&Arg> {
+        self.named_args
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, arg)| arg)
+    }
+
+    /// fill the placeholders of the named arg (e.g. `${name}`) with
+    /// a value which isn't interpreted as markdown
+    pub fn apply_named(
+        &self,
+        composite: &mut Composite<'a>,
+        name: &str,
+        value: &'a str,
+    ) {
+        self.fill(composite, self.named_arg(name), value);
+    }
+
+    /// fill the placeholders of the named arg (e.g. `${name}`) with
+    /// a value interpreted as markdown
+    ///
+    /// As for `apply_md`, the compounds following the placeholders
+    /// must be filled before.
+    pub fn apply_named_md(
+        &self,
+        composite: &mut Composite<'a>,
+        name: &str,
+        md: &'a str,
+    ) {
+        self.fill_md(composite, self.named_arg(name), md);
+    }
 }
 
 /// build an inline from a string literal intepreted as markdown and
-/// optional arguments which may fill places designed as `$0`..`$9`
+/// optional arguments which may fill places designed as `$0`, `$1`, etc.
 ///
 /// Differences with parsing a string built with `format!`:
 /// * the arguments aren't interpreted as markdown, which is convenient to insert user supplied
@@ -100,6 +271,9 @@ impl<'a> InlineTemplate<'a> {
 /// * arguments can be omited, repeated, or given in arbitrary order
 /// * no support for fmt parameters or arguments other than `&str`
 ///
+/// The `minimad-macros` crate has a version of this macro which parses
+/// and checks the template at compile time.
+///
 /// Example:
 /// ```
 /// use minimad::*;
@@ -142,9 +316,9 @@ mod tests {
 
     #[test]
     fn simple_template_parsing() {
-        let mut args = <[Arg; 10]>::default();
-        args[0].add(3);
-        args[1].add(1);
+        let mut args = vec![(1, Arg::default()), (0, Arg::default())];
+        args[0].1.add(1);
+        args[1].1.add(3);
         assert_eq!(
             InlineTemplate::from("test $1 and $0"),
             InlineTemplate {
@@ -155,13 +329,15 @@ mod tests {
                     Compound::raw_str("$0"),
                 ]),
                 args,
+                named_args: Vec::new(),
+                options: Options::default(),
             },
         );
 
-        let mut args = <[Arg; 10]>::default();
-        args[0].add(1);
-        args[2].add(0);
-        args[2].add(2);
+        let mut args = vec![(2, Arg::default()), (0, Arg::default())];
+        args[0].1.add(0);
+        args[0].1.add(2);
+        args[1].1.add(1);
         assert_eq!(
             InlineTemplate::from("$2$0$2 "), // repetition and hole
             InlineTemplate {
@@ -172,10 +348,131 @@ mod tests {
                     Compound::raw_str(" "),
                 ]),
                 args,
+                named_args: Vec::new(),
+                options: Options::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn multi_digit_and_named_args() {
+        let mut args = vec![(12, Arg::default()), (1, Arg::default())];
+        args[0].1.add(0);
+        args[1].1.add(4);
+        let mut name_arg = Arg::default();
+        name_arg.add(2);
+        assert_eq!(
+            InlineTemplate::from("$12 ${name} $1$ ${Bad} ${}"),
+            InlineTemplate {
+                composite: Composite::from(vec![
+                    Compound::raw_str("$12"),
+                    Compound::raw_str(" "),
+                    Compound::raw_str("${name}"),
+                    Compound::raw_str(" "),
+                    Compound::raw_str("$1"),
+                    Compound::raw_str("$ ${Bad} ${}"),
+                ]),
+                args,
+                named_args: vec![("name", name_arg)],
+                options: Options::default(),
             },
         );
     }
 
+    #[test]
+    fn large_positional_index() {
+        let template = InlineTemplate::from("Price: $10000000 only, not $1000000000");
+        assert_eq!(template.args.len(), 2);
+        let mut composite = template.raw_composite();
+        template.apply(&mut composite, 10000000, "3 coins");
+        template.apply(&mut composite, 5, "nothing");
+        assert_eq!(
+            composite,
+            Composite::from(vec![
+                Compound::raw_str("Price: "),
+                Compound::raw_str("3 coins"),
+                Compound::raw_str(" only, not "),
+                Compound::raw_str("$1000000000"),
+            ]),
+        );
+    }
+
+    #[test]
+    fn placeholders_are_filled_by_index() {
+        let template = InlineTemplate::from("$0 and ${name}");
+        let mut composite = template.raw_composite();
+        template.apply(&mut composite, 0, "a");
+        template.apply(&mut composite, 0, "b");
+        template.apply_named(&mut composite, "name", "c");
+        template.apply_named_md(&mut composite, "name", "*d*");
+        assert_eq!(
+            composite,
+            Composite::from(vec![
+                Compound::raw_str("b"),
+                Compound::raw_str(" and "),
+                Compound::raw_str("d").italic(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn named_and_md_composition() {
+        let template = InlineTemplate::from("*${what}*: $0 and ${what}, $1");
+        let mut composite = template.raw_composite();
+        template.apply_named(&mut composite, "what", "some *thing*");
+        template.apply_named(&mut composite, "unknown", "x");
+        template.apply(&mut composite, 1, "c");
+        template.apply_md(&mut composite, 0, "**a** `b`");
+        assert_eq!(
+            composite,
+            Composite::from(vec![
+                Compound::raw_str("some *thing*").italic(),
+                Compound::raw_str(": "),
+                Compound::raw_str("a").bold(),
+                Compound::raw_str(" "),
+                Compound::raw_str("b").code(),
+                Compound::raw_str(" and "),
+                Compound::raw_str("some *thing*"),
+                Compound::raw_str(", "),
+                Compound::raw_str("c"),
+            ]),
+        );
+        let mut composite = template.raw_composite();
+        template.apply_named_md(&mut composite, "what", "~~x~~ y");
+        assert_eq!(
+            composite,
+            Composite::from(vec![
+                Compound::raw_str("x").strikeout(),
+                Compound::raw_str(" y"),
+                Compound::raw_str(": "),
+                Compound::raw_str("$0"),
+                Compound::raw_str(" and "),
+                Compound::raw_str("x").strikeout(),
+                Compound::raw_str(" y"),
+                Compound::raw_str(", "),
+                Compound::raw_str("$1"),
+            ]),
+        );
+    }
+
+    #[test]
+    fn underscore_emphasis_option() {
+        let options = Options::default().underscore_emphasis(true);
+        let template = InlineTemplate::with_options("_${name}_: $0", options);
+        let mut composite = template.raw_composite();
+        template.apply_named(&mut composite, "name", "my_var");
+        template.apply_md(&mut composite, 0, "__big__ snake_case");
+        assert_eq!(
+            composite,
+            Composite::from(vec![
+                Compound::raw_str("my_var").italic(),
+                Compound::raw_str(": "),
+                Compound::raw_str("big").bold(),
+                Compound::raw_str(" snake_case"),
+            ]),
+        );
+    }
+
     #[test]
     fn simple_composition() {
         let template = InlineTemplate::from("using $1 and **$0**");
@@ -1,15 +1,26 @@
+mod formatters;
 mod inline_template;
 mod owning_template_expander;
+#[cfg(feature = "serde")]
+mod serde_filling;
 mod tbl_builder;
+mod template_error;
+mod template_registry;
 mod text_template;
 
 pub use {
+    formatters::Formatters,
     inline_template::*,
     owning_template_expander::*,
     tbl_builder::*,
+    template_error::*,
+    template_registry::*,
     text_template::{
+        Placeholder,
         SubTemplateExpander,
+        SubTemplateRange,
         TextTemplate,
         TextTemplateExpander,
+        is_valid_name_char,
     },
 };
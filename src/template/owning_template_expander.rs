@@ -36,10 +36,28 @@ enum FillingOperation<'s> {
         name: &'s str,
         sub_expander: OwningSubTemplateExpander<'s>,
     },
+    SetFlag {
+        name: &'s str,
+        value: bool,
+    },
 }
 enum SubFillingOperation<'s> {
-    Set { name: &'s str, value: String },
-    SetMD { name: &'s str, value: String },
+    Set {
+        name: &'s str,
+        value: String,
+    },
+    SetMD {
+        name: &'s str,
+        value: String,
+    },
+    SetFlag {
+        name: &'s str,
+        value: bool,
+    },
+    Sub {
+        name: &'s str,
+        sub_expander: OwningSubTemplateExpander<'s>,
+    },
 }
 
 impl<'s> OwningTemplateExpander<'s> {
@@ -133,12 +151,66 @@ impl<'s> OwningTemplateExpander<'s> {
         self
     }
 
+    /// set whether the conditional blocks with name `name` are kept
+    pub fn set_flag(
+        &mut self,
+        name: &'s str,
+        value: bool,
+    ) -> &mut Self {
+        self.ops.push(FillingOperation::SetFlag { name, value });
+        self
+    }
+
     /// build a text by applying the replacements to the initial template
     pub fn expand<'t>(
         &'s self,
         template: &'t TextTemplate<'s>,
     ) -> Text<'s> {
-        let mut expander = template.expander();
+        self.filled_expander(template, None).expand()
+    }
+
+    /// build a text by applying the replacements to the initial template,
+    /// or return an error if the names don't match the ones of the template
+    /// (see [`TextTemplateExpander::expand_strict`])
+    pub fn expand_strict<'t>(
+        &'s self,
+        template: &'t TextTemplate<'s>,
+    ) -> Result<Text<'s>, TemplateError> {
+        self.filled_expander(template, None).expand_strict()
+    }
+
+    /// build a text by applying the replacements to the initial template,
+    /// with the error `expand_strict` would return, which may be empty
+    #[cfg(feature = "serde")]
+    pub(crate) fn expand_checked<'t>(
+        &'s self,
+        template: &'t TextTemplate<'s>,
+    ) -> (Text<'s>, TemplateError) {
+        self.filled_expander(template, None).build()
+    }
+
+    /// build a text by applying the replacements to the initial template,
+    /// and the formatters to the values of placeholders like `${size|bytes}`
+    ///
+    /// The formatted values are kept in the registry until its next use,
+    /// so that the expanded text can borrow them.
+    pub fn expand_with_formatters<'t>(
+        &'s self,
+        template: &'t TextTemplate<'s>,
+        formatters: &'s mut Formatters,
+    ) -> Text<'s> {
+        self.filled_expander(template, Some(formatters)).expand()
+    }
+
+    fn filled_expander<'t>(
+        &'s self,
+        template: &'t TextTemplate<'s>,
+        formatters: Option<&'s mut Formatters>,
+    ) -> TextTemplateExpander<'s, 't> {
+        let mut expander = match formatters {
+            Some(formatters) => template.expander_with_formatters(formatters),
+            None => template.expander(),
+        };
         if let Some(s) = &self.default_value {
             expander.set_all(s);
         }
@@ -157,21 +229,23 @@ impl<'s> OwningTemplateExpander<'s> {
                     expander.set_lines_md(name, value);
                 }
                 FillingOperation::Sub { name, sub_expander } => {
-                    let sub = expander.sub(name);
-                    for op in &sub_expander.ops {
-                        match op {
-                            SubFillingOperation::Set { name, value } => {
-                                sub.set(name, value);
-                            }
-                            SubFillingOperation::SetMD { name, value } => {
-                                sub.set_md(name, value);
-                            }
-                        }
-                    }
+                    sub_expander.fill(expander.sub(name));
+                }
+                FillingOperation::SetFlag { name, value } => {
+                    expander.set_flag(name, *value);
                 }
             }
         }
-        expander.expand()
+        expander
+    }
+
+    /// build a text by applying the replacements to the initial template,
+    /// not borrowing the template nor the expander
+    pub fn expand_owned(
+        &'s self,
+        template: &TextTemplate<'s>,
+    ) -> OwnedText {
+        self.expand(template).into_owned()
     }
 }
 
@@ -219,4 +293,58 @@ impl<'s> OwningSubTemplateExpander<'s> {
         });
         self
     }
+
+    /// set whether the conditional blocks with name `name` in the
+    /// sub template are kept
+    pub fn set_flag(
+        &mut self,
+        name: &'s str,
+        value: bool,
+    ) -> &mut Self {
+        self.ops.push(SubFillingOperation::SetFlag { name, value });
+        self
+    }
+
+    /// return an expander for a sub template nested in this one
+    pub fn sub(
+        &mut self,
+        name: &'s str,
+    ) -> &mut OwningSubTemplateExpander<'s> {
+        let idx = self.ops.len();
+        self.ops.push(SubFillingOperation::Sub {
+            name,
+            sub_expander: OwningSubTemplateExpander::new(),
+        });
+        match &mut self.ops[idx] {
+            SubFillingOperation::Sub {
+                name: _,
+                sub_expander,
+            } => sub_expander,
+            _ => unreachable!(),
+        }
+    }
+
+    fn fill<'b>(
+        &'s self,
+        sub: &mut SubTemplateExpander<'s, 'b>,
+    ) where
+        's: 'b,
+    {
+        for op in &self.ops {
+            match op {
+                SubFillingOperation::Set { name, value } => {
+                    sub.set(name, value);
+                }
+                SubFillingOperation::SetMD { name, value } => {
+                    sub.set_md(name, value);
+                }
+                SubFillingOperation::SetFlag { name, value } => {
+                    sub.set_flag(name, *value);
+                }
+                SubFillingOperation::Sub { name, sub_expander } => {
+                    sub_expander.fill(sub.sub(name));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,352 @@
+use {
+    crate::*,
+    serde::Serialize,
+    serde_json::{
+        Map,
+        Value,
+    },
+};
+
+/// what can be filled from the fields of an object
+trait Fill<'s> {
+    fn set_value(
+        &mut self,
+        name: &'s str,
+        value: String,
+    );
+    fn set_flag_value(
+        &mut self,
+        name: &'s str,
+        value: bool,
+    );
+    fn sub_expander(
+        &mut self,
+        name: &'s str,
+    ) -> &mut OwningSubTemplateExpander<'s>;
+}
+
+impl<'s> Fill<'s> for OwningTemplateExpander<'s> {
+    fn set_value(
+        &mut self,
+        name: &'s str,
+        value: String,
+    ) {
+        self.set(name, value);
+    }
+    fn set_flag_value(
+        &mut self,
+        name: &'s str,
+        value: bool,
+    ) {
+        self.set_flag(name, value);
+    }
+    fn sub_expander(
+        &mut self,
+        name: &'s str,
+    ) -> &mut OwningSubTemplateExpander<'s> {
+        self.sub(name)
+    }
+}
+
+impl<'s> Fill<'s> for OwningSubTemplateExpander<'s> {
+    fn set_value(
+        &mut self,
+        name: &'s str,
+        value: String,
+    ) {
+        self.set(name, value);
+    }
+    fn set_flag_value(
+        &mut self,
+        name: &'s str,
+        value: bool,
+    ) {
+        self.set_flag(name, value);
+    }
+    fn sub_expander(
+        &mut self,
+        name: &'s str,
+    ) -> &mut OwningSubTemplateExpander<'s> {
+        self.sub(name)
+    }
+}
+
+/// return the value as it should be written in place of a placeholder,
+/// if it's a scalar
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn fill<'s, F: Fill<'s>>(
+    expander: &mut F,
+    object: &'s Map<String, Value>,
+) {
+    for (name, value) in object {
+        match value {
+            Value::Null => {}
+            Value::Bool(b) => {
+                expander.set_value(name, b.to_string());
+                expander.set_flag_value(name, *b);
+            }
+            Value::Array(items) => {
+                for item in items {
+                    let sub = expander.sub_expander(name);
+                    match item {
+                        Value::Object(object) => fill(sub, object),
+                        _ => {
+                            if let Some(s) = scalar_string(item) {
+                                sub.set(name, s);
+                            }
+                        }
+                    }
+                }
+            }
+            Value::Object(object) => {
+                fill(expander.sub_expander(name), object);
+            }
+            _ => {
+                if let Some(s) = scalar_string(value) {
+                    expander.set_value(name, s);
+                }
+            }
+        }
+    }
+}
+
+/// tell whether the data gives an array to the sub template whose path,
+/// from the outermost enclosing sub template, is `path`, in every object
+/// filling its enclosing sub template (so it's only unexpanded because
+/// some arrays are empty)
+fn gives_arrays(
+    value: &Value,
+    path: &[&str],
+) -> bool {
+    let (name, path) = match path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    match (value.get(name), path.is_empty()) {
+        (Some(value), true) => value.is_array(),
+        (Some(Value::Array(items)), false) => items.iter().all(|item| gives_arrays(item, path)),
+        (Some(value @ Value::Object(_)), false) => gives_arrays(value, path),
+        _ => false,
+    }
+}
+
+/// return the names of the sub templates enclosing the one at `idx`,
+/// from the outermost one, followed by its own name
+fn sub_template_path<'s>(
+    subs: &[SubTemplateRange<'s>],
+    idx: usize,
+) -> Vec<&'s str> {
+    let mut path = vec![subs[idx].name];
+    let mut sub = &subs[idx];
+    while let Some(parent) = sub.parent {
+        let enclosing = subs.iter().find(|s| {
+            s.name == parent
+                && s.line_range.start <= sub.line_range.start
+                && sub.line_range.end <= s.line_range.end
+        });
+        match enclosing {
+            Some(enclosing) => {
+                path.insert(0, parent);
+                sub = enclosing;
+            }
+            None => break,
+        }
+    }
+    path
+}
+
+impl<'s> OwningTemplateExpander<'s> {
+    /// build an expander filled from a json object.
+    ///
+    /// Scalar fields fill the placeholders of the same name, booleans also
+    /// decide the conditional blocks, arrays expand the sub templates of
+    /// the same name once per item, and objects expand them once.
+    /// An array of scalars fills the placeholder with the name of the sub
+    /// template.
+    pub fn from_json(value: &'s Value) -> Self {
+        let mut expander = Self::new();
+        if let Value::Object(object) = value {
+            fill(&mut expander, object);
+        }
+        expander
+    }
+}
+
+impl TextTemplate<'_> {
+    /// expand the template with the fields of a json object (see
+    /// [`OwningTemplateExpander::from_json`]), or return an error if
+    /// some placeholders or sub templates weren't given data.
+    ///
+    /// Fields which aren't used by the template aren't reported.
+    ///
+    /// ```
+    /// use {minimad::*, serde_json::json};
+    ///
+    /// let template = TextTemplate::from("# ${title}\n${items\n* ${name}: ${price}\n}");
+    /// let data = json!({
+    ///     "title": "Menu",
+    ///     "items": [
+    ///         { "name": "tea", "price": 2 },
+    ///         { "name": "cake", "price": 4.5 },
+    ///     ],
+    /// });
+    /// let text = template.expand_json(&data).unwrap();
+    /// assert_eq!(text.as_text().to_string(), "# Menu\n* tea: 2\n* cake: 4.5\n");
+    /// let error = template.expand_json(&json!({ "items": [] })).unwrap_err();
+    /// assert_eq!(error.unfilled_placeholders, vec!["title".to_string()]);
+    /// ```
+    pub fn expand_json(
+        &self,
+        value: &Value,
+    ) -> Result<OwnedText, TemplateError> {
+        if !value.is_object() {
+            return Err(TemplateError {
+                invalid_data: Some("not an object".to_string()),
+                ..Default::default()
+            });
+        }
+        let expander = OwningTemplateExpander::from_json(value);
+        let (text, mut error) = expander.expand_checked(self);
+        // the data may have more fields than needed, and an empty
+        // array isn't missing data
+        error.unknown_names.clear();
+        let subs = self.sub_templates();
+        error.unexpanded_sub_templates.retain(|name| {
+            !(0..subs.len())
+                .filter(|&idx| subs[idx].name == name)
+                .any(|idx| gives_arrays(value, &sub_template_path(&subs, idx)))
+        });
+        if error.is_empty() {
+            Ok(text.into_owned())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// expand the template with data which serializes as an object, like
+    /// a struct or a map, the same way than with `expand_json`
+    pub fn expand_serialize<T: Serialize + ?Sized>(
+        &self,
+        data: &T,
+    ) -> Result<OwnedText, TemplateError> {
+        match serde_json::to_value(data) {
+            Ok(value) => self.expand_json(&value),
+            Err(e) => Err(TemplateError {
+                invalid_data: Some(e.to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::*,
+        serde_json::json,
+        std::collections::BTreeMap,
+    };
+
+    static MD: &str = r#"# ${title}
+${?draft
+*draft*
+}
+${sections
+## ${name}
+${lines
+* ${lines}
+}
+}"#;
+
+    #[test]
+    fn expand_from_json() {
+        let template = TextTemplate::from(MD);
+        let data = json!({
+            "title": "Notes",
+            "draft": false,
+            "author": "unused",
+            "sections": [
+                { "name": "A", "lines": ["a1", "a2"] },
+                { "name": "B", "lines": [] },
+            ],
+        });
+        assert_eq!(
+            template.expand_json(&data).unwrap().as_text().to_string(),
+            "# Notes\n## A\n* a1\n* a2\n## B\n",
+        );
+        let data = json!({
+            "draft": true,
+            "sections": [{ "lines": ["x"] }],
+        });
+        assert_eq!(
+            template.expand_json(&data),
+            Err(TemplateError {
+                unfilled_placeholders: vec!["title".to_string(), "name".to_string()],
+                ..Default::default()
+            }),
+        );
+        assert!(template.expand_json(&json!([1])).is_err());
+    }
+
+    #[test]
+    fn empty_arrays_at_their_level() {
+        let template = TextTemplate::from(MD);
+        // an empty array named like the nested sub template, but at the
+        // top level, doesn't fill it
+        let data = json!({
+            "title": "Notes",
+            "lines": [],
+            "sections": [{ "name": "A" }],
+        });
+        assert_eq!(
+            template.expand_json(&data),
+            Err(TemplateError {
+                unexpanded_sub_templates: vec!["lines".to_string()],
+                ..Default::default()
+            }),
+        );
+        // every section must give its lines
+        let data = json!({
+            "title": "Notes",
+            "sections": [
+                { "name": "A", "lines": [] },
+                { "name": "B" },
+            ],
+        });
+        assert!(template.expand_json(&data).is_err());
+        let data = json!({
+            "title": "Notes",
+            "sections": { "name": "A", "lines": [] },
+        });
+        assert_eq!(
+            template.expand_json(&data).unwrap().as_text().to_string(),
+            "# Notes\n## A\n",
+        );
+    }
+
+    #[test]
+    fn expand_from_serialize() {
+        let template = TextTemplate::from("${title}: ${count}");
+        let mut data = BTreeMap::new();
+        data.insert("title", "Total");
+        data.insert("count", "3");
+        assert_eq!(
+            template
+                .expand_serialize(&data)
+                .unwrap()
+                .as_text()
+                .to_string(),
+            "Total: 3\n",
+        );
+        let error = template.expand_serialize("not a map").unwrap_err();
+        assert_eq!(error.to_string(), "invalid data: not an object");
+    }
+}
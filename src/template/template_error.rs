@@ -0,0 +1,70 @@
+use std::fmt;
+
+/// the problems found by a strict expansion of a template
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    /// names which were set but which aren't in the template
+    pub unknown_names: Vec<String>,
+    /// names of the placeholders which were left in the text
+    pub unfilled_placeholders: Vec<String>,
+    /// names of the sub templates which were never expanded
+    pub unexpanded_sub_templates: Vec<String>,
+    /// why the data given to fill the template couldn't be used
+    pub invalid_data: Option<String>,
+}
+
+impl TemplateError {
+    pub fn is_empty(&self) -> bool {
+        self.unknown_names.is_empty()
+            && self.unfilled_placeholders.is_empty()
+            && self.unexpanded_sub_templates.is_empty()
+            && self.invalid_data.is_none()
+    }
+}
+
+fn write_names(
+    f: &mut fmt::Formatter<'_>,
+    first: &mut bool,
+    label: &str,
+    names: &[String],
+) -> fmt::Result {
+    if names.is_empty() {
+        return Ok(());
+    }
+    if !*first {
+        write!(f, "; ")?;
+    }
+    *first = false;
+    write!(f, "{}: {}", label, names.join(", "))
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        let mut first = true;
+        write_names(f, &mut first, "unknown names", &self.unknown_names)?;
+        write_names(
+            f,
+            &mut first,
+            "unfilled placeholders",
+            &self.unfilled_placeholders,
+        )?;
+        write_names(
+            f,
+            &mut first,
+            "unexpanded sub templates",
+            &self.unexpanded_sub_templates,
+        )?;
+        if let Some(reason) = &self.invalid_data {
+            if !first {
+                write!(f, "; ")?;
+            }
+            write!(f, "invalid data: {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TemplateError {}
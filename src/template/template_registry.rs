@@ -0,0 +1,212 @@
+use {
+    super::text_template::{
+        TemplateBuilder,
+        read_include_token,
+    },
+    crate::*,
+    std::fmt,
+};
+
+/// why a template of a registry couldn't be built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncludeError {
+    /// no template was registered with this name
+    UnknownTemplate(String),
+    /// the templates include each other, in this order
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnknownTemplate(name) => write!(f, "unknown template: {name}"),
+            Self::Cycle(names) => write!(f, "include cycle: {}", names.join(" > ")),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+/// a set of named templates which can include each other with
+/// lines like `${>footer}`.
+///
+/// The included lines are inserted as if they were written in place
+/// of the include line, so they can be part of a sub template or of
+/// a conditional block.
+///
+/// ```
+/// use minimad::*;
+///
+/// let mut registry = TemplateRegistry::new();
+/// registry
+///     .add("footer", "*see ${url}*")
+///     .add("help", "# ${title}\n${>footer}");
+/// let template = registry.template("help").unwrap();
+/// let mut expander = template.expander();
+/// expander.set("title", "Help").set("url", "the site");
+/// assert_eq!(expander.expand().to_string(), "# Help\n*see the site*\n");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TemplateRegistry<'s> {
+    sources: Vec<(&'s str, &'s str)>,
+    options: Options,
+}
+
+impl<'s> TemplateRegistry<'s> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// set the parsing options of the templates
+    pub fn set_options(
+        &mut self,
+        options: Options,
+    ) -> &mut Self {
+        self.options = options;
+        self
+    }
+    /// add a template, replacing the one with the same name, if any
+    pub fn add(
+        &mut self,
+        name: &'s str,
+        md: &'s str,
+    ) -> &mut Self {
+        self.sources.retain(|(n, _)| *n != name);
+        self.sources.push((name, md));
+        self
+    }
+    /// return the source of the template with this name
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Option<&'s str> {
+        self.sources
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, md)| *md)
+    }
+    /// build the template with this name, with its includes resolved
+    pub fn template(
+        &self,
+        name: &str,
+    ) -> Result<TextTemplate<'s>, IncludeError> {
+        let mut builder = TemplateBuilder::with_options(self.options);
+        self.add_lines(&mut builder, name, &mut Vec::new())?;
+        Ok(builder.build())
+    }
+    /// check that all templates can be built, i.e. that there's no
+    /// include of an unknown template and no include cycle
+    pub fn check(&self) -> Result<(), IncludeError> {
+        for (name, _) in &self.sources {
+            self.add_lines(&mut TemplateBuilder::default(), name, &mut Vec::new())?;
+        }
+        Ok(())
+    }
+    fn add_lines(
+        &self,
+        builder: &mut TemplateBuilder<'s>,
+        name: &str,
+        stack: &mut Vec<&'s str>, // the templates being included
+    ) -> Result<(), IncludeError> {
+        let (name, md) = match self.sources.iter().find(|(n, _)| *n == name) {
+            Some(source) => *source,
+            None => {
+                return Err(IncludeError::UnknownTemplate(name.to_string()));
+            }
+        };
+        if stack.contains(&name) {
+            let mut names: Vec<String> = stack.iter().map(|n| n.to_string()).collect();
+            names.push(name.to_string());
+            return Err(IncludeError::Cycle(names));
+        }
+        stack.push(name);
+        for md_line in clean::lines(md) {
+            match read_include_token(md_line) {
+                Some(included) => self.add_lines(builder, included, stack)?,
+                None => builder.add_line(md_line),
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn includes_in_sub_templates() {
+        let mut registry = TemplateRegistry::new();
+        registry
+            .add("row", "|${name}|${size}|")
+            .add("table", "|name|size|\n|-|-:|\n${files\n${>row}\n}")
+            .add(
+                "screen",
+                r#"
+                # ${title}
+                ${>table}
+                ${?more
+                ${>footer}
+                }
+            "#,
+            )
+            .add("footer", "*more to come*");
+        let template = registry.template("screen").unwrap();
+        let mut expander = template.expander();
+        expander.set("title", "Files").set_flag("more", true);
+        expander.sub("files").set("name", "a").set("size", "3");
+        expander.sub("files").set("name", "b").set("size", "5");
+        let text = expander.expand();
+        assert_eq!(text.lines.len(), 6);
+        assert_eq!(
+            text.to_string(),
+            TextTemplate::from("# Files\n|name|size|\n|-|-:|\n|a|3|\n|b|5|\n*more to come*")
+                .expander()
+                .expand()
+                .to_string(),
+        );
+        // without registry, the include line is kept as is
+        let template = TextTemplate::from("${>footer}");
+        assert_eq!(template.expander().expand().to_string(), "${>footer}\n");
+    }
+
+    #[test]
+    fn include_errors() {
+        let mut registry = TemplateRegistry::new();
+        registry
+            .add("a", "A\n${>b}")
+            .add("b", "B\n${>c}")
+            .add("c", "C\n${>a}")
+            .add("d", "D\n${>e}");
+        assert_eq!(
+            registry.template("a").err(),
+            Some(IncludeError::Cycle(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+                "a".to_string(),
+            ])),
+        );
+        assert_eq!(
+            registry.template("d").err(),
+            Some(IncludeError::UnknownTemplate("e".to_string())),
+        );
+        assert!(registry.check().is_err());
+        registry.add("c", "C").add("e", "E");
+        assert!(registry.check().is_ok());
+        // the same template can be included several times
+        registry.add("f", "${>e}\n${>e}");
+        assert_eq!(
+            registry
+                .template("f")
+                .unwrap()
+                .expander()
+                .expand()
+                .to_string(),
+            "E\nE\n",
+        );
+    }
+}
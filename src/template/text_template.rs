@@ -1,10 +1,34 @@
-use crate::*;
+use {
+    crate::*,
+    std::{
+        collections::HashMap,
+        ops::Range,
+    },
+};
 
 #[derive(Debug, Default)]
 struct SubTemplate<'s> {
     start_line_idx: usize,
     line_count: usize,
     name: &'s str,
+    parent: Option<usize>, // index of the enclosing sub template
+}
+
+/// a block of lines only kept when a condition is met, with
+/// optional alternate lines kept when it's not
+#[derive(Debug, Default)]
+struct Conditional<'s> {
+    start_line_idx: usize,
+    then_line_count: usize,
+    else_line_count: usize,
+    name: &'s str,
+    sub: Option<usize>, // index of the enclosing sub template
+}
+
+#[derive(Debug)]
+enum OpenBlock<'s> {
+    Sub(usize),                         // index of the sub template
+    Conditional(Conditional<'s>, bool), // true when in the else branch
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -13,22 +37,66 @@ struct CompoundArg<'s> {
     line_idx: usize,      // index in the line in the template's text
     composite_idx: usize, // for when the line is multi-composite (ie it's a tablerow)
     compound_idx: usize,  // index of the compound in the line's composite
+    formatter: Option<&'s str>,
+    formatter_arg: Option<&'s str>,
+    fallback: Option<Vec<Compound<'s>>>, // used when no value is given
 }
 
 /// a markdown template allowing you to replace some placeholders with
 /// given values, or to expand some sub-templates with repetitions
 /// (useful with lists, table rows, etc.)
+///
+/// A placeholder may name a formatter, with an optional argument, like
+/// `${size|bytes}` or `${ratio|percent:1}`. The formatter is applied to the
+/// value by expanders built with `expander_with_formatters`.
+///
+/// A placeholder may also hold a fallback, used when no value is given,
+/// like `${name:-unknown}` or `${name:-*unknown*}`.
+///
+/// A sub-template starts with a `${some-name` line and ends with a `}` line.
+/// Sub-templates can be nested.
+///
+/// A conditional block starts with a `${?some-name` line and ends with a `}`
+/// line. It's kept only when a flag or a value was set with this name, or
+/// a sub-template with this name was expanded. It may contain a `}{` line,
+/// after which come the lines kept when the condition isn't met.
 #[derive(Debug)]
 pub struct TextTemplate<'s> {
     pub text: Text<'s>,
     compound_args: Vec<CompoundArg<'s>>, // replacements of compounds
     sub_templates: Vec<SubTemplate<'s>>,
+    conditionals: Vec<Conditional<'s>>,
+    options: Options, // used to parse the template and the md values
+}
+
+/// a `${some-name}` placeholder of a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placeholder<'s> {
+    pub name: &'s str,
+    /// index of the line in the template's text
+    pub line_idx: usize,
+    /// name of the innermost sub template containing the placeholder
+    pub sub_template: Option<&'s str>,
+    /// name of the formatter applied to the value, as in `${size|bytes}`
+    pub formatter: Option<&'s str>,
+}
+
+/// the position of a sub template in the text of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubTemplateRange<'s> {
+    pub name: &'s str,
+    /// the lines of the sub template in the template's text
+    pub line_range: Range<usize>,
+    /// name of the enclosing sub template, if it's nested
+    pub parent: Option<&'s str>,
 }
 
 #[derive(Debug)]
 enum SubTemplateToken<'s> {
     None,
     Start(&'s str),
+    ConditionalStart(&'s str),
+    Else,
     End,
 }
 
@@ -45,31 +113,70 @@ pub struct SubTemplateExpander<'s, 'b> {
     name: &'b str,
     raw_replacements: Vec<Replacement<'s, 'b>>, // replacements which are done as non interpreted compound content
     md_replacements: Vec<Replacement<'s, 'b>>,
+    flags: Vec<(&'b str, bool)>,
+    sub_expansions: Vec<SubTemplateExpander<'s, 'b>>,
 }
 
 /// an expander you get from a template. You specify replacements
 /// on the expander then you ask it the text using `expand`
 pub struct TextTemplateExpander<'s, 'b> {
     template: &'b TextTemplate<'s>,
+    formatters: Option<&'s mut Formatters>,
+    formatted_sets: Vec<FormattedSet<'s>>, // values waiting for their formatter
+    formatted_values: HashMap<(usize, &'s str), &'s str>, // by compound arg and raw value
     text: Text<'s>,
     sub_expansions: Vec<SubTemplateExpander<'s, 'b>>,
     md_replacements: Vec<Replacement<'s, 'b>>,
     lines_to_add: Vec<Vec<Line<'s>>>,
     lines_to_exclude: Vec<bool>, // true when the line must not be copied into the final text
+    conditions: Vec<bool>,       // whether the conditions of the conditional blocks are met
+    filled: Vec<bool>,           // whether the compound args were given a value at top level
+    unknown_names: Vec<String>,  // names given at top level but not found in the template
+}
+
+/// a value set for a placeholder naming a formatter, which is
+/// formatted when expanding
+struct FormattedSet<'s> {
+    arg_idx: usize,
+    added_line: Option<usize>, // the index in the added lines, for `set_lines`
+    value: &'s str,
+}
+
+/// what's noted during expansion for a strict check
+#[derive(Default)]
+struct ExpansionChecks<'s, 'b> {
+    unknown_names: Vec<&'b str>,
+    unfilled_placeholders: Vec<&'s str>,
+    reached_subs: Vec<bool>, // whether the sub templates were in kept lines
+    expanded_subs: Vec<bool>,
 }
 
 //-------------------------------------------------------------------
 //                 Template parsing
 //-------------------------------------------------------------------
 
-fn is_valid_name_char(c: char) -> bool {
+/// tell whether the char may be part of the name of a placeholder,
+/// in a `TextTemplate` or an `InlineTemplate`
+///
+/// It's public only so that minimad-macros checks names the same way.
+#[doc(hidden)]
+pub fn is_valid_name_char(c: char) -> bool {
     c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-'
 }
 
 fn read_sub_template_token(md_line: &str) -> SubTemplateToken<'_> {
     let mut chars = md_line.chars();
-    match (chars.next(), chars.next()) {
-        (Some('$'), Some('{')) => {
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('$'), Some('{'), Some('?')) => {
+            // "${?" : maybe a conditional block opening
+            let name = &md_line[3..];
+            if !name.is_empty() && name.chars().all(is_valid_name_char) {
+                SubTemplateToken::ConditionalStart(name)
+            } else {
+                SubTemplateToken::None
+            }
+        }
+        (Some('$'), Some('{'), _) => {
             // "${" : maybe a sub-template opening
             let name = &md_line[2..];
             if !name.is_empty() && name.chars().all(is_valid_name_char) {
@@ -78,13 +185,105 @@ fn read_sub_template_token(md_line: &str) -> SubTemplateToken<'_> {
                 SubTemplateToken::None
             }
         }
-        (Some('}'), None) => SubTemplateToken::End,
+        (Some('}'), Some('{'), None) => SubTemplateToken::Else,
+        (Some('}'), None, _) => SubTemplateToken::End,
         _ => SubTemplateToken::None,
     }
 }
 
-/// find the `${some-name}` arguments in the composite, and add them
-/// to args.
+/// read the name of the template included by a line like `${>footer}`
+pub(crate) fn read_include_token(md_line: &str) -> Option<&str> {
+    let name = md_line.strip_prefix("${>")?.strip_suffix('}')?;
+    if !name.is_empty() && name.chars().all(is_valid_name_char) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// the start of a placeholder found in a compound
+struct PlaceholderStart<'s> {
+    dollar_idx: usize,
+    name: &'s str,
+    formatter: Option<&'s str>,
+    formatter_arg: Option<&'s str>,
+    fallback_idx: Option<usize>, // start of the fallback, after `:-`
+    end_idx: Option<usize>,      // index of the closing brace, if in the compound
+}
+
+/// find the first placeholder starting at or after `from` in the string
+fn read_placeholder(
+    src: &str,
+    from: usize,
+) -> Option<PlaceholderStart<'_>> {
+    let mut search_start = from;
+    while let Some(pos) = src[search_start..].find("${") {
+        let dollar_idx = search_start + pos;
+        if let Some(placeholder) = read_placeholder_at(src, dollar_idx) {
+            return Some(placeholder);
+        }
+        search_start = dollar_idx + 2;
+    }
+    None
+}
+
+fn read_placeholder_at(
+    src: &str,
+    dollar_idx: usize,
+) -> Option<PlaceholderStart<'_>> {
+    let name_start = dollar_idx + 2;
+    let mut bar_idx = None; // start of the formatter
+    let mut colon_idx = None; // start of the formatter's argument
+    for (idx, c) in src[name_start..].char_indices() {
+        let idx = name_start + idx;
+        let (fallback_idx, end_idx) = match c {
+            '}' => (None, Some(idx)),
+            ':' if bar_idx.is_some() && colon_idx.is_none() => {
+                // the argument of the formatter, which may start with a dash
+                colon_idx = Some(idx);
+                continue;
+            }
+            ':' if src[idx + 1..].starts_with('-') => {
+                let fallback_idx = idx + 2;
+                let end_idx = src[fallback_idx..].find('}').map(|len| fallback_idx + len);
+                (Some(fallback_idx), end_idx)
+            }
+            _ if colon_idx.is_some() => continue, // any char is accepted in the argument
+            '|' if bar_idx.is_none() => {
+                bar_idx = Some(idx);
+                continue;
+            }
+            _ if is_valid_name_char(c) => continue,
+            _ => return None,
+        };
+        let name_end = bar_idx.unwrap_or(idx);
+        let formatter = bar_idx.map(|bar_idx| &src[bar_idx + 1..colon_idx.unwrap_or(idx)]);
+        if name_end == name_start || formatter == Some("") {
+            return None;
+        }
+        return Some(PlaceholderStart {
+            dollar_idx,
+            name: &src[name_start..name_end],
+            formatter,
+            formatter_arg: colon_idx
+                .map(|colon_idx| &src[colon_idx + 1..idx])
+                .filter(|arg| !arg.is_empty()),
+            fallback_idx,
+            end_idx,
+        });
+    }
+    None
+}
+
+/// find the `${some-name}` arguments, possibly with a formatter as in
+/// `${some-name|formatter:arg}` or a fallback as in `${some-name:-fallback}`,
+/// in the composite, and add them to args.
+///
+/// After a formatter, the first `:` starts its argument, which may start
+/// with a dash (as in `${delta|shift:-1}`), and the fallback comes after it.
+/// The argument may be empty, as in `${size|bytes::-unknown}`.
+///
+/// As the fallback may contain markdown, it may span several compounds.
 fn find_args<'s>(
     composite: &mut Composite<'s>,
     args: &mut Vec<CompoundArg<'s>>,
@@ -92,116 +291,305 @@ fn find_args<'s>(
     composite_idx: usize,
 ) {
     let mut compounds = Vec::new();
-    for compound in &composite.compounds {
-        let mut start = 0;
-        let mut iter = compound.as_str().char_indices();
-        while let Some((_, c)) = iter.next() {
-            if c == '$' {
-                if let Some((bridx, c)) = iter.next() {
-                    if c == '{' {
-                        for (idx, c) in &mut iter {
-                            if c == '}' {
-                                if idx - bridx > 1 {
-                                    if start + 1 < bridx {
-                                        compounds.push(compound.sub(start, bridx - 1));
-                                    }
-                                    args.push(CompoundArg {
-                                        name: &compound.as_str()[bridx + 1..idx],
-                                        line_idx,
-                                        composite_idx,
-                                        compound_idx: compounds.len(),
-                                    });
-                                    compounds.push(compound.sub(bridx - 1, idx + 1)); // placeholder
-                                    start = idx + 1;
-                                }
-                                break;
-                            } else if !is_valid_name_char(c) {
-                                break;
-                            }
+    let mut idx = 0; // index of the compound in the composite
+    let mut start = 0; // start of the part of the compound not yet handled
+    while idx < composite.compounds.len() {
+        let compound = composite.compounds[idx].clone();
+        let placeholder = match read_placeholder(compound.as_str(), start) {
+            Some(placeholder) => placeholder,
+            None => {
+                let tail = compound.tail(start);
+                if !tail.is_empty() {
+                    compounds.push(tail);
+                }
+                idx += 1;
+                start = 0;
+                continue;
+            }
+        };
+        let dollar_idx = placeholder.dollar_idx;
+        let (end_compound_idx, end_idx, fallback) =
+            match (placeholder.fallback_idx, placeholder.end_idx) {
+                (fallback_idx, Some(end_idx)) => (
+                    idx,
+                    end_idx,
+                    fallback_idx.map(|fallback_idx| vec![compound.sub(fallback_idx, end_idx)]),
+                ),
+                (Some(fallback_idx), None) => {
+                    // the fallback goes on in the next compounds
+                    let mut fallback = vec![compound.tail(fallback_idx)];
+                    let mut end = None;
+                    for (next_idx, next) in composite.compounds.iter().enumerate().skip(idx + 1) {
+                        if let Some(end_idx) = next.as_str().find('}') {
+                            fallback.push(next.sub(0, end_idx));
+                            end = Some((next_idx, end_idx));
+                            break;
+                        }
+                        fallback.push(next.clone());
+                    }
+                    match end {
+                        Some((end_compound_idx, end_idx)) => {
+                            fallback.retain(|compound| !compound.is_empty());
+                            (end_compound_idx, end_idx, Some(fallback))
+                        }
+                        None => {
+                            // not closed, so not a placeholder
+                            compounds.push(compound.tail(start));
+                            idx += 1;
+                            start = 0;
+                            continue;
                         }
                     }
                 }
-            }
+                (None, None) => unreachable!(),
+            };
+        if start < dollar_idx {
+            compounds.push(compound.sub(start, dollar_idx));
         }
-        let tail = compound.tail(start);
-        if !tail.is_empty() {
-            compounds.push(tail);
+        args.push(CompoundArg {
+            name: placeholder.name,
+            line_idx,
+            composite_idx,
+            compound_idx: compounds.len(),
+            formatter: placeholder.formatter,
+            formatter_arg: placeholder.formatter_arg,
+            fallback,
+        });
+        if end_compound_idx == idx {
+            compounds.push(compound.sub(dollar_idx, end_idx + 1)); // placeholder
+        } else {
+            compounds.push(compound.tail(dollar_idx)); // start of the placeholder
         }
+        idx = end_compound_idx;
+        start = end_idx + 1;
     }
     composite.compounds = compounds;
 }
 
-impl<'s> From<&'s str> for TextTemplate<'s> {
-    /// build a template from a markdown text with placeholders like ${some-name}
-    /// and sub-templates
-    fn from(md: &'s str) -> TextTemplate<'s> {
-        let mut text = Text { lines: Vec::new() };
-        let mut compound_args = Vec::new();
-        let mut sub_templates = Vec::new();
-        let mut current_sub_template: Option<SubTemplate<'_>> = None;
-        let mut between_fences = false;
-        for md_line in clean::lines(md) {
-            match read_sub_template_token(md_line) {
-                SubTemplateToken::Start(name) => {
-                    current_sub_template = Some(SubTemplate {
-                        start_line_idx: text.lines.len(),
-                        line_count: 0,
-                        name,
-                    });
-                    continue; // so to not add the sub-tmpl opening to the text
+/// accumulates the lines of a template
+#[derive(Default)]
+pub(crate) struct TemplateBuilder<'s> {
+    text: Text<'s>,
+    compound_args: Vec<CompoundArg<'s>>,
+    sub_templates: Vec<SubTemplate<'s>>,
+    conditionals: Vec<Conditional<'s>>,
+    closed_subs: Vec<bool>, // whether each sub template was closed
+    open_blocks: Vec<OpenBlock<'s>>,
+    between_fences: bool,
+    fence_quote_depth: u8,
+    options: Options,
+}
+
+impl<'s> TemplateBuilder<'s> {
+    pub(crate) fn add_line(
+        &mut self,
+        md_line: &'s str,
+    ) {
+        let enclosing_sub = self.open_blocks.iter().rev().find_map(|block| match block {
+            OpenBlock::Sub(sub_idx) => Some(*sub_idx),
+            _ => None,
+        });
+        match read_sub_template_token(md_line) {
+            SubTemplateToken::Start(name) => {
+                self.open_blocks
+                    .push(OpenBlock::Sub(self.sub_templates.len()));
+                self.sub_templates.push(SubTemplate {
+                    start_line_idx: self.text.lines.len(),
+                    line_count: 0,
+                    name,
+                    parent: enclosing_sub,
+                });
+                self.closed_subs.push(false);
+                return; // so to not add the sub-tmpl opening to the text
+            }
+            SubTemplateToken::ConditionalStart(name) => {
+                let conditional = Conditional {
+                    start_line_idx: self.text.lines.len(),
+                    then_line_count: 0,
+                    else_line_count: 0,
+                    name,
+                    sub: enclosing_sub,
+                };
+                self.open_blocks
+                    .push(OpenBlock::Conditional(conditional, false));
+                return;
+            }
+            SubTemplateToken::Else => {
+                if let Some(OpenBlock::Conditional(conditional, in_else @ false)) =
+                    self.open_blocks.last_mut()
+                {
+                    conditional.then_line_count =
+                        self.text.lines.len() - conditional.start_line_idx;
+                    *in_else = true;
+                    return;
                 }
-                SubTemplateToken::End => {
-                    if current_sub_template.is_some() {
-                        let mut sub_template = current_sub_template.take().unwrap();
-                        sub_template.line_count = text.lines.len() - sub_template.start_line_idx;
-                        sub_templates.push(sub_template);
-                        continue; // so to not add the sub-tmpl closing to the text
+                // we'll assume this `}{` isn't part of any templating
+            }
+            SubTemplateToken::End => match self.open_blocks.pop() {
+                Some(OpenBlock::Sub(sub_idx)) => {
+                    let sub_template: &mut SubTemplate<'_> = &mut self.sub_templates[sub_idx];
+                    sub_template.line_count = self.text.lines.len() - sub_template.start_line_idx;
+                    self.closed_subs[sub_idx] = true;
+                    return; // so to not add the sub-tmpl closing to the text
+                }
+                Some(OpenBlock::Conditional(mut conditional, in_else)) => {
+                    let count = self.text.lines.len() - conditional.start_line_idx;
+                    if in_else {
+                        conditional.else_line_count = count - conditional.then_line_count;
                     } else {
-                        // we'll assume this `}` isn't part of any templating
+                        conditional.then_line_count = count;
                     }
+                    self.conditionals.push(conditional);
+                    return;
+                }
+                None => {
+                    // we'll assume this `}` isn't part of any templating
                 }
-                SubTemplateToken::None => {}
+            },
+            SubTemplateToken::None => {}
+        }
+        let line_idx = self.text.lines.len();
+        let parser = parser::LineParser::from(md_line)
+            .options(self.options)
+            .in_template();
+        let mut line = if self.between_fences {
+            parser.quoted_code(self.fence_quote_depth)
+        } else {
+            parser.line()
+        };
+        match &mut line {
+            Line::Normal(ref mut composite) => {
+                find_args(composite, &mut self.compound_args, line_idx, 0);
+                self.text.lines.push(line);
             }
-            let line_idx = text.lines.len();
-            let parser = parser::LineParser::from(md_line);
-            let mut line = if between_fences {
-                parser.as_code()
-            } else {
-                parser.line()
-            };
-            match &mut line {
-                Line::Normal(ref mut composite) => {
-                    find_args(composite, &mut compound_args, line_idx, 0);
-                    text.lines.push(line);
+            Line::TableRow(ref mut table_row) => {
+                for (composite_idx, composite) in table_row.cells.iter_mut().enumerate() {
+                    find_args(composite, &mut self.compound_args, line_idx, composite_idx);
                 }
-                Line::TableRow(ref mut table_row) => {
-                    for (composite_idx, composite) in table_row.cells.iter_mut().enumerate() {
-                        find_args(composite, &mut compound_args, line_idx, composite_idx);
+                self.text.lines.push(line);
+            }
+            Line::CodeFence(composite) => {
+                self.between_fences = !self.between_fences;
+                self.fence_quote_depth = composite.quote_depth;
+                let code_langs = &mut self.text.code_langs;
+                if self.between_fences {
+                    let lang = composite.compounds.first().map(|c| c.src);
+                    if let Some(lang) = lang.filter(|lang| !lang.is_empty()) {
+                        code_langs.push((line_idx, lang));
                     }
-                    text.lines.push(line);
-                }
-                Line::CodeFence(..) => {
-                    between_fences = !between_fences;
+                } else if code_langs.last().is_some_and(|&(idx, _)| idx == line_idx) {
+                    code_langs.pop(); // an empty block
                 }
-                _ => {
-                    text.lines.push(line);
+            }
+            _ => {
+                self.text.lines.push(line);
+            }
+        }
+    }
+
+    pub(crate) fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+    pub(crate) fn build(self) -> TextTemplate<'s> {
+        let TemplateBuilder {
+            text,
+            compound_args,
+            sub_templates,
+            mut conditionals,
+            closed_subs,
+            options,
+            ..
+        } = self;
+        // the sub templates which weren't closed are removed, their
+        // content being considered part of the enclosing block
+        let mut new_indexes = Vec::with_capacity(closed_subs.len());
+        let mut closed_count = 0;
+        for &closed in &closed_subs {
+            new_indexes.push(if closed { Some(closed_count) } else { None });
+            if closed {
+                closed_count += 1;
+            }
+        }
+        let closed_ancestor = |mut sub_idx: Option<usize>| {
+            while let Some(idx) = sub_idx {
+                if closed_subs[idx] {
+                    return new_indexes[idx];
                 }
+                sub_idx = sub_templates[idx].parent;
             }
+            None
+        };
+        let parents: Vec<Option<usize>> = sub_templates
+            .iter()
+            .map(|sub_template| closed_ancestor(sub_template.parent))
+            .collect();
+        for conditional in &mut conditionals {
+            conditional.sub = closed_ancestor(conditional.sub);
         }
+        let sub_templates = sub_templates
+            .into_iter()
+            .zip(parents)
+            .zip(&closed_subs)
+            .filter(|(_, closed)| **closed)
+            .map(|((sub_template, parent), _)| SubTemplate {
+                parent,
+                ..sub_template
+            })
+            .collect();
         TextTemplate {
             text,
             compound_args,
             sub_templates,
+            conditionals,
+            options,
         }
     }
 }
 
+impl<'s> From<&'s str> for TextTemplate<'s> {
+    /// build a template from a markdown text with placeholders like ${some-name}
+    /// and sub-templates
+    fn from(md: &'s str) -> TextTemplate<'s> {
+        Self::with_options(md, Options::default())
+    }
+}
+
 impl<'s> TextTemplate<'s> {
+    /// build a template with specific parsing options, which also
+    /// apply to the values set with `set_md` and `set_lines_md`
+    pub fn with_options(
+        md: &'s str,
+        options: Options,
+    ) -> TextTemplate<'s> {
+        let mut builder = TemplateBuilder::with_options(options);
+        for md_line in clean::lines(md) {
+            builder.add_line(md_line);
+        }
+        builder.build()
+    }
+
     /// return a new expander for the template
     pub fn expander<'b>(&'b self) -> TextTemplateExpander<'s, 'b> {
         TextTemplateExpander::from(self)
     }
 
+    /// return a new expander for the template, applying the formatters
+    /// named in the placeholders to the values
+    ///
+    /// The formatted values are kept in the registry until its next use,
+    /// so that the expanded text can borrow them.
+    pub fn expander_with_formatters<'b>(
+        &'b self,
+        formatters: &'s mut Formatters,
+    ) -> TextTemplateExpander<'s, 'b> {
+        let mut expander = TextTemplateExpander::from(self);
+        expander.formatters = Some(formatters);
+        expander
+    }
+
     /// if the line `line_idx` is part of a sub template, return this
     /// template's index. Return None if it's not part of a template.
     ///
@@ -212,14 +600,104 @@ impl<'s> TextTemplate<'s> {
         &self,
         line_idx: usize,
     ) -> Option<usize> {
-        for (sub_idx, sub_template) in self.sub_templates.iter().enumerate() {
-            if line_idx >= sub_template.start_line_idx
-                && line_idx < sub_template.start_line_idx + sub_template.line_count
-            {
-                return Some(sub_idx);
-            }
-        }
-        None
+        self.sub_templates
+            .iter()
+            .position(|sub_template| sub_template.contains(line_idx))
+    }
+
+    /// return the placeholders of the template, in order
+    pub fn placeholders(&self) -> Vec<Placeholder<'s>> {
+        self.compound_args
+            .iter()
+            .map(|compound_arg| Placeholder {
+                name: compound_arg.name,
+                line_idx: compound_arg.line_idx,
+                sub_template: self
+                    .sub_templates
+                    .iter()
+                    .rev()
+                    .find(|sub_template| sub_template.contains(compound_arg.line_idx))
+                    .map(|sub_template| sub_template.name),
+                formatter: compound_arg.formatter,
+            })
+            .collect()
+    }
+
+    /// return the sub templates, the enclosing ones before the
+    /// nested ones
+    pub fn sub_templates(&self) -> Vec<SubTemplateRange<'s>> {
+        self.sub_templates
+            .iter()
+            .map(|sub_template| SubTemplateRange {
+                name: sub_template.name,
+                line_range: sub_template.start_line_idx
+                    ..sub_template.start_line_idx + sub_template.line_count,
+                parent: sub_template
+                    .parent
+                    .map(|parent| self.sub_templates[parent].name),
+            })
+            .collect()
+    }
+
+    /// tell whether a placeholder or a conditional block uses the name,
+    /// in the given sub template or in the whole template
+    fn uses_name(
+        &self,
+        name: &str,
+        sub_idx: Option<usize>,
+    ) -> bool {
+        let in_scope = |line_idx: usize| match sub_idx {
+            Some(sub_idx) => self.sub_templates[sub_idx].contains(line_idx),
+            None => true,
+        };
+        self.compound_args
+            .iter()
+            .any(|compound_arg| compound_arg.name == name && in_scope(compound_arg.line_idx))
+            || self
+                .conditionals
+                .iter()
+                .any(|conditional| conditional.name == name && in_scope(conditional.start_line_idx))
+    }
+
+    /// tell whether there's a sub template with this name directly in the
+    /// given sub template or at top level
+    fn has_sub(
+        &self,
+        name: &str,
+        parent: Option<usize>,
+    ) -> bool {
+        self.sub_templates
+            .iter()
+            .any(|sub_template| sub_template.name == name && sub_template.parent == parent)
+    }
+}
+
+impl SubTemplate<'_> {
+    fn contains(
+        &self,
+        line_idx: usize,
+    ) -> bool {
+        line_idx >= self.start_line_idx && line_idx < self.start_line_idx + self.line_count
+    }
+}
+
+impl Conditional<'_> {
+    fn contains(
+        &self,
+        line_idx: usize,
+    ) -> bool {
+        line_idx >= self.start_line_idx
+            && line_idx < self.start_line_idx + self.then_line_count + self.else_line_count
+    }
+    /// tell whether the line, which must be in the block, is to be
+    /// removed, depending on whether the condition is met
+    fn excludes(
+        &self,
+        line_idx: usize,
+        met: bool,
+    ) -> bool {
+        let in_then = line_idx < self.start_line_idx + self.then_line_count;
+        in_then != met
     }
 }
 
@@ -238,18 +716,35 @@ impl<'s, 'b> From<&'b TextTemplate<'s>> for TextTemplateExpander<'s, 'b> {
         let line_count = template.text.lines.len();
         let lines_to_add = vec![Vec::new(); line_count];
         let lines_to_exclude = vec![false; line_count];
+        let conditions = vec![false; template.conditionals.len()];
+        let filled = vec![false; template.compound_args.len()];
         Self {
             template,
+            formatters: None,
+            formatted_sets: Vec::new(),
+            formatted_values: HashMap::new(),
             text: template.text.clone(),
             sub_expansions: Vec::new(),
             md_replacements: Vec::new(),
             lines_to_add,
             lines_to_exclude,
+            conditions,
+            filled,
+            unknown_names: Vec::new(),
         }
     }
 }
 
 impl<'s, 'b> SubTemplateExpander<'s, 'b> {
+    fn new(name: &'b str) -> Self {
+        Self {
+            name,
+            raw_replacements: Vec::new(),
+            md_replacements: Vec::new(),
+            flags: Vec::new(),
+            sub_expansions: Vec::new(),
+        }
+    }
     /// replace placeholders with name `name` with the given value, not interpreted as markdown
     pub fn set(
         &mut self,
@@ -268,6 +763,74 @@ impl<'s, 'b> SubTemplateExpander<'s, 'b> {
         self.md_replacements.push(Replacement { name, value });
         self
     }
+    /// set whether the conditional blocks with name `name` in the
+    /// sub template are kept
+    pub fn set_flag(
+        &mut self,
+        name: &'b str,
+        value: bool,
+    ) -> &mut SubTemplateExpander<'s, 'b> {
+        self.flags.push((name, value));
+        self
+    }
+    /// prepare expansion of a sub template nested in this one and
+    /// return a mutable reference to the object in which to set
+    /// compound replacements
+    pub fn sub(
+        &mut self,
+        name: &'b str,
+    ) -> &mut SubTemplateExpander<'s, 'b> {
+        let idx = self.sub_expansions.len();
+        self.sub_expansions.push(SubTemplateExpander::new(name));
+        &mut self.sub_expansions[idx]
+    }
+    /// return the markdown given here for the placeholders with this name
+    fn md_replacement(
+        &self,
+        name: &str,
+    ) -> Option<&'s str> {
+        self.md_replacements
+            .iter()
+            .find(|repl| repl.name == name)
+            .map(|repl| repl.value)
+    }
+    /// return the raw value given here for the placeholders with this name
+    fn raw_replacement(
+        &self,
+        name: &str,
+    ) -> Option<&'s str> {
+        self.raw_replacements
+            .iter()
+            .rev()
+            .find(|repl| repl.name == name)
+            .map(|repl| repl.value)
+    }
+    /// return the names of the placeholders and conditional blocks
+    /// this expansion deals with
+    fn names(&self) -> impl Iterator<Item = &'b str> + '_ {
+        self.raw_replacements
+            .iter()
+            .chain(&self.md_replacements)
+            .map(|repl| repl.name)
+            .chain(self.flags.iter().map(|(name, _)| *name))
+    }
+    /// return whether the condition with this name is met for this
+    /// expansion, if it was decided here
+    fn condition(
+        &self,
+        name: &str,
+    ) -> Option<bool> {
+        if let Some((_, value)) = self.flags.iter().rev().find(|(n, _)| *n == name) {
+            return Some(*value);
+        }
+        let set = self
+            .raw_replacements
+            .iter()
+            .chain(&self.md_replacements)
+            .any(|repl| repl.name == name)
+            || self.sub_expansions.iter().any(|sub| sub.name == name);
+        if set { Some(true) } else { None }
+    }
 }
 
 fn set_in_line<'s>(
@@ -287,64 +850,45 @@ fn set_in_line<'s>(
     }
 }
 
-fn set_in_text<'s>(
-    template: &TextTemplate<'s>,
-    text: &mut Text<'s>,
-    line_offset: usize,
-    name: Option<&str>,
+/// replace the compound of the argument with the ones of the
+/// value parsed as markdown
+fn set_md_in_line<'s>(
+    line: &mut Line<'s>,
+    compound_arg: &CompoundArg<'s>,
     value: &'s str,
+    options: Options,
 ) {
-    for compound_arg in &template.compound_args {
-        if name.is_none() || name == Some(compound_arg.name) {
-            let idx = compound_arg.line_idx;
-            if idx < line_offset || idx - line_offset >= text.lines.len() {
-                continue; // can happen if a replacement name is present in the outside text
-            }
-            set_in_line(&mut text.lines[idx - line_offset], compound_arg, value);
+    let replacing_composite = Composite::from_inline_with_options(value, options);
+    set_compounds_in_line(line, compound_arg, replacing_composite.compounds);
+}
+
+/// replace the compound of the argument with the given ones
+fn set_compounds_in_line<'s>(
+    line: &mut Line<'s>,
+    compound_arg: &CompoundArg<'s>,
+    compounds: Vec<Compound<'s>>,
+) {
+    match line {
+        Line::Normal(ref mut composite) => {
+            replace_compound(composite, compound_arg.compound_idx, compounds);
+        }
+        Line::TableRow(ref mut table_row) => {
+            replace_compound(
+                &mut table_row.cells[compound_arg.composite_idx],
+                compound_arg.compound_idx,
+                compounds,
+            );
         }
+        _ => {}
     }
 }
 
-#[allow(clippy::needless_lifetimes)]
-fn set_all_md_in_text<'s, 'b>(
-    template: &TextTemplate<'s>,
-    text: &mut Text<'s>,
-    line_offset: usize,
-    md_replacements: &[Replacement<'s, 'b>],
+fn push_unique(
+    names: &mut Vec<String>,
+    name: &str,
 ) {
-    if md_replacements.is_empty() {
-        return; // no need to iterate over all compound_args
-    }
-    for compound_arg in template.compound_args.iter().rev() {
-        let idx = compound_arg.line_idx;
-        if idx < line_offset || idx - line_offset >= text.lines.len() {
-            continue;
-        }
-        for md_repl in md_replacements {
-            if md_repl.name == compound_arg.name {
-                let replacing_composite = Composite::from_inline(md_repl.value);
-                // we replace the compound with the ones of the parsed value
-                let patched_line = &mut text.lines[idx - line_offset];
-                match patched_line {
-                    Line::Normal(ref mut composite) => {
-                        replace_compound(
-                            composite,
-                            compound_arg.compound_idx,
-                            replacing_composite.compounds,
-                        );
-                    }
-                    Line::TableRow(ref mut table_row) => {
-                        replace_compound(
-                            &mut table_row.cells[compound_arg.composite_idx],
-                            compound_arg.compound_idx,
-                            replacing_composite.compounds,
-                        );
-                    }
-                    _ => {}
-                }
-                break; // it's not possible to apply two replacements to the compound
-            }
-        }
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
     }
 }
 
@@ -373,28 +917,235 @@ impl<'s, 'b> TextTemplateExpander<'s, 'b> {
         name: &str,
         value: &'s str,
     ) -> &mut TextTemplateExpander<'s, 'b> {
-        set_in_text(self.template, &mut self.text, 0, Some(name), value);
+        for (arg_idx, compound_arg) in self.template.compound_args.iter().enumerate() {
+            if compound_arg.name == name {
+                if self.is_formatted(compound_arg) {
+                    self.formatted_sets.push(FormattedSet {
+                        arg_idx,
+                        added_line: None,
+                        value,
+                    });
+                } else {
+                    set_in_line(
+                        &mut self.text.lines[compound_arg.line_idx],
+                        compound_arg,
+                        value,
+                    );
+                }
+            }
+        }
+        self.set_filled(name);
+        self.set_condition(name, true);
+        self
+    }
+
+    /// set whether the conditional blocks with name `name` are kept.
+    ///
+    /// Setting a value or expanding a sub template with this name
+    /// also makes the condition met.
+    pub fn set_flag(
+        &mut self,
+        name: &str,
+        value: bool,
+    ) -> &mut TextTemplateExpander<'s, 'b> {
+        if !self.template.conditionals.iter().any(|c| c.name == name) {
+            self.add_unknown_name(name);
+        }
+        self.set_condition(name, value);
         self
     }
 
+    /// note that the placeholders with this name were given a value,
+    /// checking the name exists
+    fn set_filled(
+        &mut self,
+        name: &str,
+    ) {
+        for (idx, compound_arg) in self.template.compound_args.iter().enumerate() {
+            if compound_arg.name == name {
+                self.filled[idx] = true;
+            }
+        }
+        if !self.template.uses_name(name, None) {
+            self.add_unknown_name(name);
+        }
+    }
+
+    /// tell whether the values of the placeholder go through a formatter
+    fn is_formatted(
+        &self,
+        compound_arg: &CompoundArg<'s>,
+    ) -> bool {
+        self.formatters.is_some() && compound_arg.formatter.is_some()
+    }
+
+    /// return the value formatted for the placeholder `arg_idx`, or the
+    /// value itself when it doesn't go through a formatter.
+    ///
+    /// The values are formatted by `format_values` when expanding.
+    fn formatted(
+        &self,
+        arg_idx: usize,
+        value: &'s str,
+    ) -> &'s str {
+        self.formatted_values
+            .get(&(arg_idx, value))
+            .copied()
+            .unwrap_or(value)
+    }
+
+    /// format all the values given to placeholders naming a formatter,
+    /// keeping them in the formatters registry which the expanded text
+    /// borrows, then fill the placeholders set at top level
+    fn format_values(&mut self) {
+        let formatters = match self.formatters.take() {
+            Some(formatters) => formatters,
+            None => return,
+        };
+        let compound_args = &self.template.compound_args;
+        let mut requests: Vec<(usize, &'s str)> = Vec::new(); // compound arg and raw value
+        for (arg_idx, compound_arg) in compound_args.iter().enumerate() {
+            if compound_arg.formatter.is_none() {
+                continue;
+            }
+            let name = compound_arg.name;
+            requests.extend(
+                self.formatted_sets
+                    .iter()
+                    .filter(|set| set.arg_idx == arg_idx)
+                    .map(|set| (arg_idx, set.value)),
+            );
+            requests.extend(
+                self.md_replacements
+                    .iter()
+                    .filter(|repl| repl.name == name)
+                    .map(|repl| (arg_idx, repl.value)),
+            );
+            let mut subs: Vec<&SubTemplateExpander<'s, 'b>> = self.sub_expansions.iter().collect();
+            while let Some(sub) = subs.pop() {
+                requests.extend(
+                    sub.raw_replacements
+                        .iter()
+                        .chain(&sub.md_replacements)
+                        .filter(|repl| repl.name == name)
+                        .map(|repl| (arg_idx, repl.value)),
+                );
+                subs.extend(&sub.sub_expansions);
+            }
+        }
+        requests.sort_unstable();
+        requests.dedup();
+        let values = formatters.format_values(requests.iter().map(|&(arg_idx, value)| {
+            let compound_arg = &compound_args[arg_idx];
+            (
+                compound_arg.formatter.unwrap_or_default(),
+                compound_arg.formatter_arg,
+                value,
+            )
+        }));
+        self.formatted_values = requests
+            .into_iter()
+            .zip(values.iter().map(String::as_str))
+            .collect();
+        for set in std::mem::take(&mut self.formatted_sets) {
+            let compound_arg = &compound_args[set.arg_idx];
+            let value = self.formatted(set.arg_idx, set.value);
+            let line = match set.added_line {
+                Some(added_idx) => &mut self.lines_to_add[compound_arg.line_idx][added_idx],
+                None => &mut self.text.lines[compound_arg.line_idx],
+            };
+            set_in_line(line, compound_arg, value);
+        }
+    }
+
+    fn add_unknown_name(
+        &mut self,
+        name: &str,
+    ) {
+        push_unique(&mut self.unknown_names, name);
+    }
+
+    fn set_condition(
+        &mut self,
+        name: &str,
+        value: bool,
+    ) {
+        for (idx, conditional) in self.template.conditionals.iter().enumerate() {
+            if conditional.name == name {
+                self.conditions[idx] = value;
+            }
+        }
+    }
+
+    /// tell whether the line must be removed because it's in a conditional
+    /// block whose condition isn't met, or in the alternate lines of a
+    /// block whose condition is met.
+    ///
+    /// Only the conditional blocks directly in the sub template `sub_idx`
+    /// (or outside any sub template when it's `None`) are checked. They're
+    /// decided by the innermost of the sub expansions being built which
+    /// deals with their name, or globally.
+    fn is_excluded_by_conditions(
+        &self,
+        line_idx: usize,
+        sub_idx: Option<usize>,
+        sub_expansions: &[&SubTemplateExpander<'s, 'b>],
+    ) -> bool {
+        self.template
+            .conditionals
+            .iter()
+            .enumerate()
+            .filter(|(_, conditional)| conditional.sub == sub_idx)
+            .filter(|(_, conditional)| conditional.contains(line_idx))
+            .any(|(idx, conditional)| {
+                let met = sub_expansions
+                    .iter()
+                    .rev()
+                    .find_map(|sub_expansion| sub_expansion.condition(conditional.name))
+                    .unwrap_or(self.conditions[idx]);
+                conditional.excludes(line_idx, met)
+            })
+    }
+
     /// replace all placeholders with the given value, non interpreted
     /// (i.e. stars, backquotes, etc. don't mess the styling defined by the template).
     /// This can be used at start to have a "default" value.
+    ///
+    /// Placeholders with their own fallback, like `${name:-fallback}`,
+    /// aren't changed.
     pub fn set_all(
         &mut self,
         value: &'s str,
     ) -> &mut TextTemplateExpander<'s, 'b> {
-        set_in_text(self.template, &mut self.text, 0, None, value);
+        for (idx, compound_arg) in self.template.compound_args.iter().enumerate() {
+            if compound_arg.fallback.is_none() {
+                set_in_line(
+                    &mut self.text.lines[compound_arg.line_idx],
+                    compound_arg,
+                    value,
+                );
+                self.filled[idx] = true;
+            }
+        }
+        let compound_args = &self.template.compound_args;
+        self.formatted_sets.retain(|set| {
+            set.added_line.is_some() || compound_args[set.arg_idx].fallback.is_some()
+        });
         self
     }
 
     /// replace placeholders with name `name` with the given value, interpreted as markdown
+    ///
+    /// This also applies to the placeholders of the sub templates, unless
+    /// their sub expansion gives them some markdown.
     pub fn set_md(
         &mut self,
         name: &'b str,
         value: &'s str,
     ) -> &mut TextTemplateExpander<'s, 'b> {
         self.md_replacements.push(Replacement { name, value });
+        self.set_filled(name);
+        self.set_condition(name, true);
         self
     }
 
@@ -405,17 +1156,28 @@ impl<'s, 'b> TextTemplateExpander<'s, 'b> {
         name: &'b str,
         raw_lines: &'s str,
     ) -> &mut TextTemplateExpander<'s, 'b> {
-        for compound_arg in &self.template.compound_args {
+        for (arg_idx, compound_arg) in self.template.compound_args.iter().enumerate() {
             if compound_arg.name == name {
                 // the line holding the compound is now considered a template, it's removed
                 self.lines_to_exclude[compound_arg.line_idx] = true;
+                let formatted = self.is_formatted(compound_arg);
                 for value in clean::lines(raw_lines) {
+                    let lines_to_add = &mut self.lines_to_add[compound_arg.line_idx];
+                    if formatted {
+                        self.formatted_sets.push(FormattedSet {
+                            arg_idx,
+                            added_line: Some(lines_to_add.len()),
+                            value,
+                        });
+                    }
                     let mut line = self.text.lines[compound_arg.line_idx].clone();
                     set_in_line(&mut line, compound_arg, value);
-                    self.lines_to_add[compound_arg.line_idx].push(line);
+                    lines_to_add.push(line);
                 }
             }
         }
+        self.set_filled(name);
+        self.set_condition(name, true);
         self
     }
 
@@ -425,15 +1187,21 @@ impl<'s, 'b> TextTemplateExpander<'s, 'b> {
         name: &'b str,
         md: &'s str,
     ) -> &mut TextTemplateExpander<'s, 'b> {
+        let options = self.template.options;
         for compound_arg in &self.template.compound_args {
             if compound_arg.name == name {
                 // the line holding the compound is now considered a template, it's removed
                 self.lines_to_exclude[compound_arg.line_idx] = true;
-                for line in md.lines().map(|md| parser::LineParser::from(md).line()) {
+                for line in md
+                    .lines()
+                    .map(|md| parser::LineParser::from(md).options(options).line())
+                {
                     self.lines_to_add[compound_arg.line_idx].push(line);
                 }
             }
         }
+        self.set_filled(name);
+        self.set_condition(name, true);
         self
     }
 
@@ -443,61 +1211,204 @@ impl<'s, 'b> TextTemplateExpander<'s, 'b> {
         &mut self,
         name: &'b str,
     ) -> &mut SubTemplateExpander<'s, 'b> {
-        let sub = SubTemplateExpander {
-            name,
-            raw_replacements: Vec::new(),
-            md_replacements: Vec::new(),
-        };
+        if !self.template.has_sub(name, None) {
+            self.add_unknown_name(name);
+        }
+        self.set_condition(name, true);
         let idx = self.sub_expansions.len();
-        self.sub_expansions.push(sub);
+        self.sub_expansions.push(SubTemplateExpander::new(name));
         &mut self.sub_expansions[idx]
     }
 
+    /// build the lines of the expansions of the sub template `sub_idx`
+    /// among the given ones, which belong to the enclosing sub expansion
+    /// or to this expander. `parents` are the sub expansions being built
+    /// from the outermost one.
+    fn expand_sub(
+        &self,
+        sub_idx: usize,
+        sub_expansions: &[SubTemplateExpander<'s, 'b>],
+        parents: &[&SubTemplateExpander<'s, 'b>],
+        checks: &mut ExpansionChecks<'s, 'b>,
+    ) -> Vec<Line<'s>> {
+        let sub_templates = &self.template.sub_templates;
+        let sub_template = &sub_templates[sub_idx];
+        let start = sub_template.start_line_idx;
+        let end = start + sub_template.line_count;
+        let mut lines = Vec::new();
+        for sub_expansion in sub_expansions {
+            if sub_expansion.name != sub_template.name {
+                continue;
+            }
+            checks.expanded_subs[sub_idx] = true;
+            for name in sub_expansion.names() {
+                if !self.template.uses_name(name, Some(sub_idx)) {
+                    checks.unknown_names.push(name);
+                }
+            }
+            for child_expansion in &sub_expansion.sub_expansions {
+                if !self.template.has_sub(child_expansion.name, Some(sub_idx)) {
+                    checks.unknown_names.push(child_expansion.name);
+                }
+            }
+            let mut stack = parents.to_vec();
+            stack.push(sub_expansion);
+            for line_idx in start..end {
+                if self.is_excluded_by_conditions(line_idx, Some(sub_idx), &stack) {
+                    continue;
+                }
+                let mut in_child = false;
+                for (child_idx, child) in sub_templates.iter().enumerate() {
+                    if child.parent != Some(sub_idx) || !child.contains(line_idx) {
+                        continue;
+                    }
+                    in_child = true;
+                    if child.start_line_idx == line_idx {
+                        checks.reached_subs[child_idx] = true;
+                        lines.extend(self.expand_sub(
+                            child_idx,
+                            &sub_expansion.sub_expansions,
+                            &stack,
+                            checks,
+                        ));
+                    }
+                }
+                if !in_child {
+                    lines.push(self.fill_sub_line(line_idx, &stack, checks));
+                }
+            }
+        }
+        lines
+    }
+
+    /// build a line of a sub template.
+    ///
+    /// Markdown replacements win over raw ones: the ones of the innermost
+    /// sub expansion giving one, then the ones of the expander. Then come
+    /// the raw replacements of the innermost sub expansion giving one.
+    fn fill_sub_line(
+        &self,
+        line_idx: usize,
+        sub_expansions: &[&SubTemplateExpander<'s, 'b>],
+        checks: &mut ExpansionChecks<'s, 'b>,
+    ) -> Line<'s> {
+        let mut line = self.text.lines[line_idx].clone();
+        let compound_args = self
+            .template
+            .compound_args
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, compound_arg)| compound_arg.line_idx == line_idx);
+        for (arg_idx, compound_arg) in compound_args {
+            let name = compound_arg.name;
+            let replacement = sub_expansions
+                .iter()
+                .rev()
+                .find_map(|sub_expansion| sub_expansion.md_replacement(name))
+                .or_else(|| {
+                    // the md replacements of the expander are applied
+                    // to the lines of the sub templates too
+                    self.md_replacements
+                        .iter()
+                        .find(|repl| repl.name == name)
+                        .map(|repl| repl.value)
+                })
+                .map(|value| (value, true))
+                .or_else(|| {
+                    sub_expansions
+                        .iter()
+                        .rev()
+                        .find_map(|sub_expansion| sub_expansion.raw_replacement(name))
+                        .map(|value| (value, false))
+                });
+            match replacement {
+                Some((value, true)) => {
+                    set_md_in_line(
+                        &mut line,
+                        compound_arg,
+                        self.formatted(arg_idx, value),
+                        self.template.options,
+                    );
+                }
+                Some((value, false)) => {
+                    set_in_line(&mut line, compound_arg, self.formatted(arg_idx, value));
+                }
+                None if self.filled[arg_idx] => {}
+                None => match &compound_arg.fallback {
+                    Some(fallback) => {
+                        set_compounds_in_line(&mut line, compound_arg, fallback.clone())
+                    }
+                    None => checks.unfilled_placeholders.push(compound_arg.name),
+                },
+            }
+        }
+        line
+    }
+
     /// build a text by applying the replacements to the initial template
-    pub fn expand(mut self) -> Text<'s> {
+    pub fn expand(self) -> Text<'s> {
+        self.build().0
+    }
+
+    /// build a text by applying the replacements to the initial template,
+    /// or return an error if names which aren't in the template were set,
+    /// if some placeholders of the text weren't filled, or if some sub
+    /// templates were never expanded
+    pub fn expand_strict(self) -> Result<Text<'s>, TemplateError> {
+        let (text, error) = self.build();
+        if error.is_empty() {
+            Ok(text)
+        } else {
+            Err(error)
+        }
+    }
+
+    /// build the text, and the error `expand_strict` would return
+    /// if it's not empty
+    pub(crate) fn build(mut self) -> (Text<'s>, TemplateError) {
         // The simple replacements defined with expander.set(name, value) have
-        // already be done at this point.
-
-        // We triage the md_replacements: we can directly apply the ones which
-        // are not applied to sub templates and we must defer the other ones
-        // to the sub templates expansion phase
-        let mut defered_repls: Vec<Vec<Replacement<'_, '_>>> =
-            vec![Vec::new(); self.template.sub_templates.len()];
-        for compound_arg in self.template.compound_args.iter().rev() {
+        // already be done at this point, except the ones needing a formatter.
+        self.format_values();
+
+        // The md replacements of lines outside sub templates can be applied
+        // now, the other ones are applied during sub templates expansion
+        // The fallbacks of the placeholders which weren't given a value are
+        // applied at the same time.
+        for (idx, compound_arg) in self.template.compound_args.iter().enumerate().rev() {
             let line_idx = compound_arg.line_idx;
-            let sub_idx = self.template.get_sub_of_line(line_idx);
-            for md_repl in &self.md_replacements {
-                if md_repl.name == compound_arg.name {
-                    if let Some(sub_idx) = sub_idx {
-                        // we must clone because a repl can be applied several times
-                        defered_repls[sub_idx].push(md_repl.clone());
-                    } else {
-                        let replacing_composite = Composite::from_inline(md_repl.value);
-                        // we replace the compound with the ones of the parsed value
-                        let patched_line = &mut self.text.lines[line_idx];
-                        match patched_line {
-                            Line::Normal(ref mut composite) => {
-                                replace_compound(
-                                    composite,
-                                    compound_arg.compound_idx,
-                                    replacing_composite.compounds,
-                                );
-                            }
-                            Line::TableRow(ref mut table_row) => {
-                                replace_compound(
-                                    &mut table_row.cells[compound_arg.composite_idx],
-                                    compound_arg.compound_idx,
-                                    replacing_composite.compounds,
-                                );
-                            }
-                            _ => {}
-                        }
-                        break; // it's not possible to apply two replacements to the compound
-                    }
-                }
+            if self.template.get_sub_of_line(line_idx).is_some() {
+                continue;
+            }
+            let md_repl = self
+                .md_replacements
+                .iter()
+                .find(|md_repl| md_repl.name == compound_arg.name);
+            if let Some(md_repl) = md_repl {
+                // it's not possible to apply two replacements to the compound
+                let value = self.formatted(idx, md_repl.value);
+                set_md_in_line(
+                    &mut self.text.lines[line_idx],
+                    compound_arg,
+                    value,
+                    self.template.options,
+                );
+            } else if let (false, Some(fallback)) = (self.filled[idx], &compound_arg.fallback) {
+                set_compounds_in_line(
+                    &mut self.text.lines[line_idx],
+                    compound_arg,
+                    fallback.clone(),
+                );
             }
         }
 
+        let sub_count = self.template.sub_templates.len();
+        let mut checks = ExpansionChecks {
+            reached_subs: vec![false; sub_count],
+            expanded_subs: vec![false; sub_count],
+            ..Default::default()
+        };
+        let sub_expansions = std::mem::take(&mut self.sub_expansions);
         for (sub_idx, sub_template) in self.template.sub_templates.iter().enumerate() {
             let start = sub_template.start_line_idx;
             let end = start + sub_template.line_count;
@@ -505,45 +1416,446 @@ impl<'s, 'b> TextTemplateExpander<'s, 'b> {
             for idx in start..end {
                 self.lines_to_exclude[idx] = true;
             }
-            for sub_expansion in &self.sub_expansions {
-                if sub_expansion.name != sub_template.name {
-                    continue;
-                }
-                let mut sub_text = Text::default();
-                for line in &self.text.lines[start..end] {
-                    sub_text.lines.push(line.clone());
-                }
-                for repl in &sub_expansion.raw_replacements {
-                    set_in_text(
-                        self.template,
-                        &mut sub_text,
-                        sub_template.start_line_idx,
-                        Some(repl.name),
-                        repl.value,
-                    );
-                }
-                let mut md_replacements = sub_expansion.md_replacements.clone();
-                md_replacements.extend(defered_repls[sub_idx].clone());
-                set_all_md_in_text(
-                    self.template,
-                    &mut sub_text,
-                    sub_template.start_line_idx,
-                    &md_replacements,
-                );
-                for line in sub_text.lines.drain(..) {
-                    self.lines_to_add[sub_template.start_line_idx].push(line);
-                }
+            if sub_template.parent.is_some() || start == end {
+                continue; // nested sub templates are expanded with their parent
+            }
+            if self.is_excluded_by_conditions(start, None, &[]) {
+                continue; // the expansions wouldn't be kept
             }
+            checks.reached_subs[sub_idx] = true;
+            let lines = self.expand_sub(sub_idx, &sub_expansions, &[], &mut checks);
+            self.lines_to_add[start].extend(lines);
         }
 
         // we now do the removals and insertions we deffered until then.
         let mut lines = Vec::new();
-        for (idx, line) in self.text.lines.drain(..).enumerate() {
+        let mut code_langs = Vec::new();
+        let text_lines = std::mem::take(&mut self.text.lines);
+        for (idx, line) in text_lines.into_iter().enumerate() {
+            if self.is_excluded_by_conditions(idx, None, &[]) {
+                continue;
+            }
             if !self.lines_to_exclude[idx] {
+                if let Some(lang) = self.text.code_lang(idx) {
+                    code_langs.push((lines.len(), lang));
+                }
                 lines.push(line);
             }
             lines.append(&mut self.lines_to_add[idx]);
         }
-        Text { lines }
+        let mut error = TemplateError {
+            unknown_names: std::mem::take(&mut self.unknown_names),
+            ..Default::default()
+        };
+        for name in checks.unknown_names {
+            push_unique(&mut error.unknown_names, name);
+        }
+        for (idx, compound_arg) in self.template.compound_args.iter().enumerate() {
+            let line_idx = compound_arg.line_idx;
+            if !self.filled[idx]
+                && compound_arg.fallback.is_none()
+                && self.template.get_sub_of_line(line_idx).is_none()
+                && !self.is_excluded_by_conditions(line_idx, None, &[])
+            {
+                push_unique(&mut error.unfilled_placeholders, compound_arg.name);
+            }
+        }
+        for name in checks.unfilled_placeholders {
+            push_unique(&mut error.unfilled_placeholders, name);
+        }
+        for (idx, sub_template) in self.template.sub_templates.iter().enumerate() {
+            if checks.reached_subs[idx] && !checks.expanded_subs[idx] {
+                push_unique(&mut error.unexpanded_sub_templates, sub_template.name);
+            }
+        }
+        (Text { lines, code_langs }, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    static MD: &str = r#"
+# Report
+${?warnings
+## Warnings:
+${warnings
+* ${warning}
+${?detail
+  * ${detail}
+}
+}
+}{
+No warning
+}
+${?footer
+---
+}
+"#;
+
+    #[test]
+    fn conditional_blocks() {
+        let template = TextTemplate::from(MD);
+        // no condition met
+        let text = template.expander().expand();
+        assert_eq!(text, Text::from("# Report\nNo warning"),);
+        // conditions met by a sub template expansion, a value and a flag
+        let mut expander = template.expander();
+        expander
+            .sub("warnings")
+            .set("warning", "disk almost full")
+            .set("detail", "sda: 98%");
+        expander.sub("warnings").set("warning", "high load");
+        expander.set_flag("footer", true);
+        assert_eq!(
+            expander.expand(),
+            Text::from(
+                "# Report\n## Warnings:\n* disk almost full\n  * sda: 98%\n* high load\n---"
+            ),
+        );
+    }
+
+    #[test]
+    fn owning_expander_flags() {
+        let template = TextTemplate::from(MD);
+        let mut expander = OwningTemplateExpander::new();
+        expander.set_flag("footer", true);
+        expander
+            .sub("warnings")
+            .set("warning", "high load")
+            .set_flag("detail", false);
+        assert_eq!(
+            expander.expand(&template),
+            Text::from("# Report\n## Warnings:\n* high load\n---"),
+        );
+    }
+
+    static NESTED_MD: &str = r#"
+# ${title}
+${groups
+## ${group}
+|name|size|
+|-|-:|
+${files
+|${name}|${size}|
+}
+${?empty
+*no file*
+}
+}
+"#;
+
+    #[test]
+    fn nested_sub_templates() {
+        let template = TextTemplate::from(NESTED_MD);
+        let mut expander = template.expander();
+        expander.set("title", "Files");
+        let sources = expander.sub("groups");
+        sources.set("group", "sources");
+        sources.sub("files").set("name", "lib.rs").set("size", "12");
+        sources
+            .sub("files")
+            .set("name", "main.rs")
+            .set_md("size", "*unknown*");
+        expander
+            .sub("groups")
+            .set("group", "docs")
+            .set_flag("empty", true);
+        assert_eq!(
+            expander.expand(),
+            Text::from(
+                r#"# Files
+## sources
+|name|size|
+|-|-:|
+|lib.rs|12|
+|main.rs|*unknown*|
+## docs
+|name|size|
+|-|-:|
+*no file*"#
+            ),
+        );
+    }
+
+    #[test]
+    fn md_replacements_precedence() {
+        let template = TextTemplate::from("${files\n* ${name}: ${size}\n}");
+        let mut expander = template.expander();
+        // the markdown given to the expander wins over the raw values
+        // of the sub expansions, but not over their markdown
+        expander
+            .set_md("size", "*unknown*")
+            .set_md("name", "*file*");
+        expander.sub("files").set("name", "a").set("size", "1");
+        expander
+            .sub("files")
+            .set_md("name", "**b**")
+            .set("size", "2");
+        assert_eq!(
+            expander.expand(),
+            Text::from("* *file*: *unknown*\n* **b**: *unknown*"),
+        );
+    }
+
+    #[test]
+    fn owning_expander_nested_subs() {
+        let template = TextTemplate::from(NESTED_MD);
+        let mut expander = OwningTemplateExpander::new();
+        expander.set("title", "Files");
+        let group = expander.sub("groups");
+        group.set("group", "sources");
+        for (name, size) in &[("lib.rs", 12), ("main.rs", 3)] {
+            group.sub("files").set("name", name).set("size", size);
+        }
+        assert_eq!(
+            expander.expand(&template),
+            Text::from("# Files\n## sources\n|name|size|\n|-|-:|\n|lib.rs|12|\n|main.rs|3|"),
+        );
+    }
+
+    #[test]
+    fn template_introspection() {
+        let template = TextTemplate::from(NESTED_MD);
+        let placeholders = template.placeholders();
+        assert_eq!(
+            placeholders
+                .iter()
+                .map(|p| (p.name, p.line_idx, p.sub_template))
+                .collect::<Vec<_>>(),
+            vec![
+                ("title", 0, None),
+                ("group", 1, Some("groups")),
+                ("name", 4, Some("files")),
+                ("size", 4, Some("files")),
+            ],
+        );
+        assert_eq!(
+            template.sub_templates(),
+            vec![
+                SubTemplateRange {
+                    name: "groups",
+                    line_range: 1..6,
+                    parent: None,
+                },
+                SubTemplateRange {
+                    name: "files",
+                    line_range: 4..5,
+                    parent: Some("groups"),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn strict_expansion() {
+        let template = TextTemplate::from(NESTED_MD);
+        // everything filled
+        let mut expander = template.expander();
+        expander.set("title", "Files");
+        expander
+            .sub("groups")
+            .set("group", "sources")
+            .sub("files")
+            .set("name", "lib.rs")
+            .set("size", "12");
+        assert!(expander.expand_strict().is_ok());
+        // problems everywhere
+        let mut expander = template.expander();
+        expander.set("titel", "Files");
+        let group = expander.sub("groups");
+        group.set("group", "sources").set_flag("empty", true);
+        group.sub("files").set("name", "lib.rs").set("sise", "12");
+        expander.sub("groups").set("group", "docs");
+        expander.sub("folders");
+        assert_eq!(
+            expander.expand_strict(),
+            Err(TemplateError {
+                unknown_names: vec![
+                    "titel".to_string(),
+                    "folders".to_string(),
+                    "sise".to_string()
+                ],
+                unfilled_placeholders: vec!["title".to_string(), "size".to_string()],
+                unexpanded_sub_templates: vec![],
+                invalid_data: None,
+            }),
+        );
+        // with a default value, all placeholders are filled
+        let mut expander = OwningTemplateExpander::new();
+        expander
+            .set_default("?")
+            .sub("groups")
+            .set("group", "empty");
+        let error = expander.expand_strict(&template).unwrap_err();
+        assert_eq!(error.to_string(), "unexpanded sub templates: files");
+    }
+
+    #[test]
+    fn formatted_placeholders() {
+        let md =
+            "# ${title|upper} (${ratio|percent:0})\n${files\n* ${name|shout}: ${size|bytes:2}\n}";
+        let template = TextTemplate::from(md);
+        assert_eq!(
+            template
+                .placeholders()
+                .iter()
+                .map(|p| (p.name, p.formatter))
+                .collect::<Vec<_>>(),
+            vec![
+                ("title", Some("upper")),
+                ("ratio", Some("percent")),
+                ("name", Some("shout")),
+                ("size", Some("bytes")),
+            ],
+        );
+        let mut formatters = Formatters::standard();
+        formatters.add("shout", |s, _| format!("{s}!"));
+        let mut expander = template.expander_with_formatters(&mut formatters);
+        expander.set("title", "files").set("ratio", "0.5");
+        expander
+            .sub("files")
+            .set_md("name", "*a*")
+            .set("size", "3000");
+        expander.sub("files").set("name", "b").set("size", "12");
+        assert_eq!(
+            expander.expand().to_string(),
+            "# FILES (50%)\n* *a*!: 2.93 KiB\n* b!: 12 B\n",
+        );
+        // without formatters, the values are kept as is
+        let mut expander = template.expander();
+        expander.set("title", "files").set("ratio", "0.5");
+        assert_eq!(expander.expand().to_string(), "# files (0.5)\n");
+        // with the owning expander
+        let mut formatters = Formatters::standard();
+        let mut expander = OwningTemplateExpander::new();
+        expander.set("title", "files").set("ratio", 0.25);
+        expander.sub("files").set("name", "c").set("size", 1 << 20);
+        assert_eq!(
+            expander
+                .expand_with_formatters(&template, &mut formatters)
+                .to_string(),
+            "# FILES (25%)\n* c: 1.00 MiB\n", // no shout formatter
+        );
+    }
+
+    #[test]
+    fn formatted_placeholders_in_tables() {
+        let template =
+            TextTemplate::from("|name|size|\n|-|-:|\n${files\n|${name}|${size|bytes}|\n}");
+        assert_eq!(
+            template
+                .placeholders()
+                .iter()
+                .map(|p| (p.name, p.formatter))
+                .collect::<Vec<_>>(),
+            vec![("name", None), ("size", Some("bytes"))],
+        );
+        let mut formatters = Formatters::standard();
+        let mut expander = template.expander_with_formatters(&mut formatters);
+        expander.sub("files").set("name", "a").set("size", "2048");
+        assert_eq!(
+            expander.expand().to_string(),
+            "|name|size|\n|-|-:|\n|a|2.0 KiB|\n",
+        );
+        // outside templates, the pipe still ends the cell
+        match Line::from("|${size|bytes}|") {
+            Line::TableRow(row) => assert_eq!(row.cells.len(), 2),
+            line => panic!("unexpected line: {:?}", line),
+        }
+    }
+
+    #[test]
+    fn formatter_arguments_starting_with_a_dash() {
+        let template = TextTemplate::from("${x|shift:-1} ${y|shift:-2:-none}");
+        let mut formatters = Formatters::new();
+        formatters.add("shift", |s, arg| format!("{s}{}", arg.unwrap_or("?")));
+        let mut expander = template.expander_with_formatters(&mut formatters);
+        expander.set("x", "a");
+        assert_eq!(expander.expand().to_string(), "a-1 none\n");
+    }
+
+    #[test]
+    fn reused_formatters() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Formatters>();
+        assert_sync::<OwningTemplateExpander<'static>>();
+        let template = TextTemplate::from("${size|bytes}: ${name}\n${lines|upper}");
+        let mut formatters = Formatters::standard();
+        for (size, expected) in [("1", "1 B"), ("2048", "2.0 KiB")] {
+            let mut expander = template.expander_with_formatters(&mut formatters);
+            expander.set("size", size).set_lines("lines", "a\nb");
+            expander.set("name", "c");
+            assert_eq!(
+                expander.expand().to_string(),
+                format!("{expected}: c\nA\nB\n"),
+            );
+        }
+        // the values set before `set_all` are replaced, like the other ones
+        let mut expander = template.expander_with_formatters(&mut formatters);
+        expander.set("size", "3").set("name", "d").set_all("?");
+        expander.set("name", "e");
+        assert_eq!(expander.expand().to_string(), "?: e\n?\n");
+    }
+
+    #[test]
+    fn placeholder_fallbacks() {
+        let md = "Owner: ${owner:-nobody}, size: ${size|bytes::-*unknown*}\n${files\n* ${name:-**unnamed** file}\n}";
+        let template = TextTemplate::from(md);
+        assert_eq!(
+            template
+                .placeholders()
+                .iter()
+                .map(|p| (p.name, p.formatter))
+                .collect::<Vec<_>>(),
+            vec![("owner", None), ("size", Some("bytes")), ("name", None)],
+        );
+        let mut expander = template.expander();
+        expander.sub("files").set("name", "a");
+        expander.sub("files");
+        assert_eq!(
+            expander.expand().to_string(),
+            "Owner: nobody, size: *unknown*\n* a\n* **unnamed** file\n",
+        );
+        // the given values replace the fallbacks
+        let mut formatters = Formatters::standard();
+        let mut expander = template.expander_with_formatters(&mut formatters);
+        expander.set("owner", "root").set("size", "2048");
+        expander.sub("files");
+        assert_eq!(
+            expander.expand_strict().map(|text| text.to_string()),
+            Ok("Owner: root, size: 2.0 KiB\n* **unnamed** file\n".to_string()),
+        );
+        // the default value of the owning expander doesn't replace them
+        let mut expander = OwningTemplateExpander::new();
+        expander.set_default("?");
+        expander.sub("files");
+        assert_eq!(
+            expander.expand(&template).to_string(),
+            "Owner: nobody, size: *unknown*\n* **unnamed** file\n",
+        );
+    }
+
+    #[cfg(feature = "escaping")]
+    #[test]
+    fn underscore_emphasis_option() {
+        let options = Options::default().underscore_emphasis(true);
+        let template = TextTemplate::with_options("# _${title}_\n${body}\n${lines}", options);
+        let mut expander = template.expander();
+        expander
+            .set("title", "a_title")
+            .set_md("body", "__bold__ snake_case")
+            .set_lines_md("lines", "_a_\n- __b__");
+        assert_eq!(
+            expander.expand().to_string(),
+            "# *a_title*\n**bold** snake_case\n*a*\n* **b**\n",
+        );
+        // without the option, underscores are kept, and escaped when
+        // they could be read as emphasis
+        let template = TextTemplate::from("_${title}_");
+        let mut expander = template.expander();
+        expander.set("title", "a");
+        assert_eq!(expander.expand().to_string(), "\\_a_\n");
     }
 }